@@ -0,0 +1,143 @@
+// src/capture.rs
+
+//! --capture：从成功响应中提取一个值，注入到同一个并发任务之后发送的请求的 Header 中，
+//! 用于登录后携带令牌访问接口这类简单的会话流程。规则格式为 `<来源> -> header:<名称>[=<模板>]`：
+//!
+//! ```text
+//! json:$.token -> header:Authorization=Bearer {}
+//! json:$.data.items[0].id -> header:X-Item-ID
+//! header:X-Session -> header:X-Session
+//! ```
+//!
+//! - 来源 `json:<路径>` 从响应体 (需要是未压缩的 JSON) 中按路径取值，路径由 `$` 开头，
+//!   依次用 `.字段` 和 `[下标]` 访问；字符串取其内容，数字和布尔值取其文本，对象和数组取其 JSON 文本，null 视为未找到
+//! - 来源 `header:<名称>` 取响应 Header 的值
+//! - 模板中的 `{}` 会被替换为提取到的值，省略模板时直接使用提取到的值
+//!
+//! 响应中没有找到值时保留之前提取到的值。
+
+use reqwest::header::HeaderMap;
+use serde_json::Value;
+
+/// 一条提取规则
+#[derive(Clone, Debug, PartialEq)]
+pub struct CaptureRule {
+    source: CaptureSource,
+    header: String,           // 注入的请求 Header
+    template: Option<String>, // 包含 {} 的值模板
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum CaptureSource {
+    Json(Vec<PathSegment>),
+    Header(String),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum PathSegment {
+    Field(String),
+    Index(usize),
+}
+
+impl CaptureRule {
+    /// 注入的请求 Header 名称
+    pub fn header(&self) -> &str {
+        &self.header
+    }
+
+    /// 从响应中提取值，没有找到时返回 None
+    pub fn extract(&self, headers: &HeaderMap, body: &[u8]) -> Option<String> {
+        match &self.source {
+            CaptureSource::Header(name) => headers.get(name.as_str()).and_then(|value| value.to_str().ok()).map(str::to_string),
+            CaptureSource::Json(path) => {
+                let root: Value = serde_json::from_slice(body).ok()?;
+                let mut value = &root;
+                for segment in path {
+                    value = match segment {
+                        PathSegment::Field(name) => value.get(name)?,
+                        PathSegment::Index(index) => value.get(index)?,
+                    };
+                }
+                match value {
+                    Value::Null => None,
+                    Value::String(s) => Some(s.clone()),
+                    other => Some(other.to_string()),
+                }
+            }
+        }
+    }
+
+    /// 按模板生成注入的 Header 值
+    pub fn header_value(&self, captured: &str) -> String {
+        match &self.template {
+            Some(template) => template.replace("{}", captured),
+            None => captured.to_string(),
+        }
+    }
+}
+
+/// 解析一条 --capture 规则
+pub fn parse_capture(s: &str) -> Result<CaptureRule, String> {
+    let (source, target) = s
+        .split_once("->")
+        .ok_or_else(|| format!("无效的 --capture 规则: \"{}\" (期望格式为 \"<来源> -> header:<名称>\")", s))?;
+    let source = match source.trim().split_once(':') {
+        Some(("json", path)) => CaptureSource::Json(parse_json_path(path.trim())?),
+        Some(("header", name)) if !name.trim().is_empty() => CaptureSource::Header(name.trim().to_string()),
+        _ => return Err(format!("无效的 --capture 来源: \"{}\" (应为 json:<路径> 或 header:<名称>)", source.trim())),
+    };
+    let target = target
+        .trim()
+        .strip_prefix("header:")
+        .ok_or_else(|| format!("无效的 --capture 目标: \"{}\" (应为 header:<名称>)", target.trim()))?;
+    let (header, template) = match target.split_once('=') {
+        Some((header, template)) => (header.trim(), Some(template.to_string())),
+        None => (target.trim(), None),
+    };
+    if reqwest::header::HeaderName::from_bytes(header.as_bytes()).is_err() {
+        return Err(format!("无效的 --capture 目标 Header: \"{}\"", header));
+    }
+    Ok(CaptureRule { source, header: header.to_string(), template })
+}
+
+/// 解析 `$.a.b[0]` 形式的 JSON 路径
+fn parse_json_path(path: &str) -> Result<Vec<PathSegment>, String> {
+    let invalid = || format!("无效的 JSON 路径: \"{}\" (应以 $ 开头，例如 $.data.items[0].id)", path);
+    let mut rest = path.strip_prefix('$').ok_or_else(invalid)?;
+    let mut segments = Vec::new();
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix('.') {
+            let end = after.find(['.', '[']).unwrap_or(after.len());
+            if end == 0 {
+                return Err(invalid());
+            }
+            segments.push(PathSegment::Field(after[..end].to_string()));
+            rest = &after[end..];
+        } else if let Some(after) = rest.strip_prefix('[') {
+            let (index, remaining) = after.split_once(']').ok_or_else(invalid)?;
+            segments.push(PathSegment::Index(index.trim().parse().map_err(|_| invalid())?));
+            rest = remaining;
+        } else {
+            return Err(invalid());
+        }
+    }
+    Ok(segments)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn capture_extracts_json_path_and_applies_template() {
+        let rule = parse_capture("json:$.data.items[1].id -> header:Authorization=Bearer {}").unwrap();
+        let body = br#"{"data": {"items": [{"id": 1}, {"id": "abc"}]}}"#;
+        let headers = reqwest::header::HeaderMap::new();
+        assert_eq!(rule.header(), "Authorization");
+        assert_eq!(rule.extract(&headers, body).as_deref(), Some("abc"));
+        assert_eq!(rule.header_value("abc"), "Bearer abc");
+        assert_eq!(rule.extract(&headers, b"{}"), None);
+        assert!(parse_capture("json:token -> header:X").is_err());
+        assert!(parse_capture("json:$.token -> query:x").is_err());
+    }
+}
@@ -0,0 +1,404 @@
+// src/connection.rs
+
+//! reqwest 连接器和 DNS 解析器的包装层，用于观察连接的建立情况。
+//!
+//! 通过 `ClientBuilder::connector_layer` 挂载：连接池需要新连接时才会经过连接器，
+//! 复用已有连接的请求不会经过这里，因此可以据此区分新建连接和复用连接，也可以只对新建连接限速 (--connect-rate)。
+//!
+//! reqwest 本身不缓存 DNS 解析结果，每个新建连接都会解析一次主机名 (--spread-dns 固定的地址除外)，
+//! 通过 `ClientBuilder::dns_resolver` 挂载的 [`TimedResolver`] 统计解析次数和耗时。
+//!
+//! --tls-resumption 时 HTTPS 连接改用 [`resumption_tls_config`] 构建的 rustls 配置：会话缓存和证书验证各包一层，
+//! 在连接器的 task-local 中记录本次握手是否查询过会话缓存 (即发起了 TLS 握手) 以及是否验证了服务端证书。
+//! 复用会话的握手 (TLS 1.3 PSK、TLS 1.2 session ID / ticket) 不发送证书，据此区分完整握手和复用会话。
+
+use hdrhistogram::Histogram;
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::client::{ClientSessionMemoryCache, ClientSessionStore, Resumption, WebPkiServerVerifier, Tls12ClientSessionValue, Tls13ClientSessionValue};
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::{DigitallySignedStruct, NamedGroup, SignatureScheme};
+use std::cell::Cell;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use tower::{Layer, Service};
+
+/// --connect-rate：限制新建连接的速率，按固定间隔依次放行，不允许突发。
+/// HTTP、WebSocket 和 TCP 模式共用同一个限速器，与请求速率无关。
+/// --scenario 中步骤的 rate 也用它限制该步骤的请求速率 (每个步骤一个)
+#[derive(Debug)]
+pub struct ConnectRateLimiter {
+    interval: Duration,
+    next: Mutex<tokio::time::Instant>, // 下一个连接最早可以开始的时间
+}
+
+impl ConnectRateLimiter {
+    /// 速率必须大于 0，且换算出的间隔不能超出 Duration 的范围
+    pub fn new(rate: f64) -> Result<Self, String> {
+        let interval = Duration::try_from_secs_f64(1.0 / rate).map_err(|_| "速率过低，换算出的间隔超出范围".to_string())?;
+        Ok(ConnectRateLimiter {
+            interval,
+            next: Mutex::new(tokio::time::Instant::now()),
+        })
+    }
+
+    /// 等待到可以建立下一个连接
+    pub async fn acquire(&self) {
+        let slot = {
+            let mut next = self.next.lock().unwrap();
+            let slot = (*next).max(tokio::time::Instant::now());
+            *next = slot + self.interval;
+            slot
+        };
+        tokio::time::sleep_until(slot).await;
+    }
+}
+
+/// 统计成功建立的连接数，指定了限速器时建立连接前先等待，指定了 TLS 握手统计时按握手类型记录建立连接的耗时
+#[derive(Clone)]
+pub struct CountConnectionsLayer {
+    opened: Arc<AtomicUsize>,
+    limiter: Option<Arc<ConnectRateLimiter>>,
+    tls: Option<Arc<TlsHandshakeStats>>,
+}
+
+impl CountConnectionsLayer {
+    pub fn new(opened: Arc<AtomicUsize>, limiter: Option<Arc<ConnectRateLimiter>>, tls: Option<Arc<TlsHandshakeStats>>) -> Self {
+        CountConnectionsLayer { opened, limiter, tls }
+    }
+}
+
+impl<S> Layer<S> for CountConnectionsLayer {
+    type Service = CountConnections<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        CountConnections { inner, opened: self.opened.clone(), limiter: self.limiter.clone(), tls: self.tls.clone() }
+    }
+}
+
+#[derive(Clone)]
+pub struct CountConnections<S> {
+    inner: S,
+    opened: Arc<AtomicUsize>,
+    limiter: Option<Arc<ConnectRateLimiter>>,
+    tls: Option<Arc<TlsHandshakeStats>>,
+}
+
+impl<S, R> Service<R> for CountConnections<S>
+where
+    S: Service<R>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<S::Response, S::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: R) -> Self::Future {
+        let connecting = self.inner.call(request);
+        let opened = self.opened.clone();
+        let limiter = self.limiter.clone();
+        let tls = self.tls.clone();
+        Box::pin(async move {
+            if let Some(limiter) = limiter {
+                limiter.acquire().await;
+            }
+            let start = Instant::now();
+            let connection = match tls {
+                None => connecting.await?,
+                Some(tls) => {
+                    // TLS 握手在连接器的 future 中同步进行，会话缓存和证书验证的包装层通过 task-local 写入观察结果
+                    let (connection, handshake) = TLS_HANDSHAKE
+                        .scope(Cell::default(), async move { (connecting.await, TLS_HANDSHAKE.with(Cell::get)) })
+                        .await;
+                    let connection = connection?;
+                    if handshake.started {
+                        tls.record(start.elapsed(), !handshake.certificate_verified);
+                    }
+                    connection
+                }
+            };
+            opened.fetch_add(1, Ordering::Relaxed);
+            let _ = REQUEST_CONNECT.try_with(|cell| cell.set(Some(start.elapsed())));
+            Ok(connection)
+        })
+    }
+}
+
+/// 一次 TLS 握手的观察结果
+#[derive(Clone, Copy, Default)]
+struct TlsHandshake {
+    started: bool,              // 查询过会话缓存，即发起了 TLS 握手 (http:// 连接不会)
+    certificate_verified: bool, // 验证过服务端证书，即完整握手
+}
+
+tokio::task_local! {
+    static TLS_HANDSHAKE: Cell<TlsHandshake>;
+    static REQUEST_CONNECT: Cell<Option<Duration>>;
+}
+
+/// 执行一次请求，同时返回请求期间新建连接的耗时 (包括 DNS 解析和 TLS 握手，不含 --connect-rate 的等待)。
+/// 复用连接池中的连接时为 None；连接器只有在请求的 future 中被轮询时才能观察到，连接池在后台建立的连接不计入
+pub async fn observe_connect<F: Future>(request: F) -> (F::Output, Option<Duration>) {
+    REQUEST_CONNECT
+        .scope(Cell::new(None), async move { (request.await, REQUEST_CONNECT.with(Cell::get)) })
+        .await
+}
+
+/// 在当前连接的观察结果上做修改，不在连接器中 (没有 task-local) 时忽略
+fn observe_handshake(update: impl FnOnce(&mut TlsHandshake)) {
+    let _ = TLS_HANDSHAKE.try_with(|cell| {
+        let mut handshake = cell.get();
+        update(&mut handshake);
+        cell.set(handshake);
+    });
+}
+
+/// --tls-resumption 的握手统计，所有 Client 共享
+pub struct TlsHandshakeStats {
+    full: Mutex<Histogram<u64>>,    // 完整握手的连接建立耗时 (微秒，包括 DNS 解析和 TCP 握手)
+    resumed: Mutex<Histogram<u64>>, // 复用会话的连接建立耗时
+}
+
+/// 某一时刻的 TLS 握手统计
+pub struct TlsSnapshot {
+    pub full_micros: Histogram<u64>,
+    pub resumed_micros: Histogram<u64>,
+}
+
+impl TlsHandshakeStats {
+    pub fn new() -> Self {
+        TlsHandshakeStats {
+            full: Mutex::new(Histogram::<u64>::new(3).unwrap()),
+            resumed: Mutex::new(Histogram::<u64>::new(3).unwrap()),
+        }
+    }
+
+    fn record(&self, elapsed: Duration, resumed: bool) {
+        let histogram = if resumed { &self.resumed } else { &self.full };
+        if let Ok(mut histogram) = histogram.lock() {
+            let _ = histogram.record((elapsed.as_micros() as u64).max(1));
+        }
+    }
+
+    /// 清空统计，用于排除压测开始前 (就绪探测、预连接) 的握手。会话缓存保留，之后的连接仍可以复用这些会话
+    pub fn reset(&self) {
+        for histogram in [&self.full, &self.resumed] {
+            if let Ok(mut histogram) = histogram.lock() {
+                histogram.reset();
+            }
+        }
+    }
+
+    pub fn snapshot(&self) -> TlsSnapshot {
+        let copy = |histogram: &Mutex<Histogram<u64>>| {
+            histogram
+                .lock()
+                .map_or_else(|_| Histogram::<u64>::new(3).unwrap(), |histogram| histogram.clone())
+        };
+        TlsSnapshot { full_micros: copy(&self.full), resumed_micros: copy(&self.resumed) }
+    }
+}
+
+/// 记录每次握手查询过会话缓存的会话缓存
+#[derive(Debug)]
+struct ObservedSessionStore {
+    inner: ClientSessionMemoryCache,
+}
+
+impl ClientSessionStore for ObservedSessionStore {
+    fn set_kx_hint(&self, server_name: ServerName<'static>, group: NamedGroup) {
+        self.inner.set_kx_hint(server_name, group);
+    }
+
+    fn kx_hint(&self, server_name: &ServerName<'_>) -> Option<NamedGroup> {
+        self.inner.kx_hint(server_name)
+    }
+
+    fn set_tls12_session(&self, server_name: ServerName<'static>, value: Tls12ClientSessionValue) {
+        self.inner.set_tls12_session(server_name, value);
+    }
+
+    fn tls12_session(&self, server_name: &ServerName<'_>) -> Option<Tls12ClientSessionValue> {
+        self.inner.tls12_session(server_name)
+    }
+
+    fn remove_tls12_session(&self, server_name: &ServerName<'static>) {
+        self.inner.remove_tls12_session(server_name);
+    }
+
+    fn insert_tls13_ticket(&self, server_name: ServerName<'static>, value: Tls13ClientSessionValue) {
+        self.inner.insert_tls13_ticket(server_name, value);
+    }
+
+    // 每次握手开始时 rustls 都会先查询 TLS 1.3 的会话票据
+    fn take_tls13_ticket(&self, server_name: &ServerName<'static>) -> Option<Tls13ClientSessionValue> {
+        observe_handshake(|handshake| handshake.started = true);
+        self.inner.take_tls13_ticket(server_name)
+    }
+}
+
+/// 记录是否验证过服务端证书的证书验证器，验证本身交给 webpki
+#[derive(Debug)]
+struct ObservedVerifier {
+    inner: Arc<WebPkiServerVerifier>,
+}
+
+impl ServerCertVerifier for ObservedVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        observe_handshake(|handshake| handshake.certificate_verified = true);
+        self.inner.verify_server_cert(end_entity, intermediates, server_name, ocsp_response, now)
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.inner.supported_verify_schemes()
+    }
+}
+
+/// --tls-resumption：所有 Client 共用的 rustls 配置 (包括会话缓存) 和握手统计
+pub struct TlsResumption {
+    pub config: rustls::ClientConfig,
+    pub stats: Arc<TlsHandshakeStats>,
+}
+
+/// --tls-resumption 使用的 rustls 配置：系统根证书、启用会话缓存 (与 rustls 默认一样缓存 256 个服务端的会话)，
+/// 只协商 HTTP/1.1 (与默认的 native-tls 后端一致)。tls13 为 false 时只使用 TLS 1.2，tls12 为 false 时只使用 TLS 1.3
+pub fn resumption_tls_config(tls12: bool, tls13: bool) -> Result<rustls::ClientConfig, String> {
+    let native = rustls_native_certs::load_native_certs();
+    let mut roots = rustls::RootCertStore::empty();
+    roots.add_parsable_certificates(native.certs);
+    if roots.is_empty() {
+        return Err("没有找到可用的系统根证书".to_string());
+    }
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let versions: Vec<&'static rustls::SupportedProtocolVersion> = [(tls13, &rustls::version::TLS13), (tls12, &rustls::version::TLS12)]
+        .into_iter()
+        .filter_map(|(enabled, version)| enabled.then_some(version))
+        .collect();
+    let verifier = WebPkiServerVerifier::builder_with_provider(Arc::new(roots), provider.clone())
+        .build()
+        .map_err(|e| e.to_string())?;
+    let mut config = rustls::ClientConfig::builder_with_provider(provider)
+        .with_protocol_versions(&versions)
+        .map_err(|e| e.to_string())?
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(ObservedVerifier { inner: verifier }))
+        .with_no_client_auth();
+    config.resumption = Resumption::store(Arc::new(ObservedSessionStore { inner: ClientSessionMemoryCache::new(256) }));
+    config.alpn_protocols = vec![b"http/1.1".to_vec()];
+    Ok(config)
+}
+
+/// DNS 解析的统计，所有 Client 共享
+pub struct DnsStats {
+    lookups: AtomicUsize,
+    failures: AtomicUsize,
+    latency: Mutex<Histogram<u64>>, // 解析耗时 (微秒)
+}
+
+/// 某一时刻的 DNS 解析统计
+pub struct DnsSnapshot {
+    pub lookups: usize,
+    pub failures: usize,
+    pub latency_micros: Histogram<u64>,
+}
+
+impl DnsStats {
+    pub fn new() -> Self {
+        DnsStats {
+            lookups: AtomicUsize::new(0),
+            failures: AtomicUsize::new(0),
+            latency: Mutex::new(Histogram::<u64>::new(3).unwrap()),
+        }
+    }
+
+    fn record(&self, elapsed: Duration, success: bool) {
+        self.lookups.fetch_add(1, Ordering::Relaxed);
+        if !success {
+            self.failures.fetch_add(1, Ordering::Relaxed);
+        }
+        if let Ok(mut latency) = self.latency.lock() {
+            let _ = latency.record((elapsed.as_micros() as u64).max(1));
+        }
+    }
+
+    /// 清空统计，用于排除压测开始前 (就绪探测、预连接) 的解析
+    pub fn reset(&self) {
+        self.lookups.store(0, Ordering::Relaxed);
+        self.failures.store(0, Ordering::Relaxed);
+        if let Ok(mut latency) = self.latency.lock() {
+            latency.reset();
+        }
+    }
+
+    pub fn snapshot(&self) -> DnsSnapshot {
+        DnsSnapshot {
+            lookups: self.lookups.load(Ordering::Relaxed),
+            failures: self.failures.load(Ordering::Relaxed),
+            latency_micros: self
+                .latency
+                .lock()
+                .map_or_else(|_| Histogram::<u64>::new(3).unwrap(), |latency| latency.clone()),
+        }
+    }
+}
+
+/// 记录每次解析耗时的 DNS 解析器，与 reqwest 默认的解析器一样通过系统的 getaddrinfo 解析
+pub struct TimedResolver {
+    stats: Arc<DnsStats>,
+}
+
+impl TimedResolver {
+    pub fn new(stats: Arc<DnsStats>) -> Self {
+        TimedResolver { stats }
+    }
+}
+
+impl Resolve for TimedResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let stats = self.stats.clone();
+        let host = name.as_str().to_string();
+        Box::pin(async move {
+            let start = Instant::now();
+            // 端口由 reqwest 在连接时按 URL 填入，这里只需要地址
+            let resolved = tokio::net::lookup_host((host.as_str(), 0))
+                .await
+                .map(|addrs| addrs.collect::<Vec<_>>());
+            stats.record(start.elapsed(), resolved.is_ok());
+            let addrs: Addrs = Box::new(resolved?.into_iter());
+            Ok(addrs)
+        })
+    }
+}
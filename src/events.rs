@@ -0,0 +1,123 @@
+// src/events.rs
+
+//! --stream-json 的事件输出。各任务把序列化好的事件行放入有界队列，由单独的线程写到标准输出，
+//! 写出 (尤其是下游读取较慢的管道) 不再占用执行请求的线程。
+//!
+//! 队列容量由 --result-buffer 指定。队列满时 (下游读取跟不上) 发送事件的任务会阻塞等待，
+//! 压测随之变慢：结果不会丢失，内存占用也不会随积压无限增长。
+
+use std::io::{self, Write};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender, TryRecvError, TrySendError};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use tokio::runtime::{Handle, RuntimeFlavor};
+
+/// 事件输出队列，None 表示所有事件都已发送
+pub struct EventStream {
+    sender: SyncSender<Option<String>>,
+    writer: Mutex<Option<JoinHandle<()>>>,
+    sent: AtomicU64,
+    blocked: AtomicU64,        // 发送时队列已满、需要等待的事件数
+    blocked_micros: AtomicU64, // 等待队列腾出空间的总时间
+}
+
+/// 输出结束时的统计
+pub struct EventStreamStats {
+    pub sent: u64,
+    pub blocked: u64,
+    pub blocked_time: Duration,
+}
+
+impl EventStream {
+    /// 创建容量为 capacity 的队列并启动写出线程
+    pub fn start(capacity: usize) -> Self {
+        let (sender, receiver) = mpsc::sync_channel(capacity);
+        let writer = std::thread::spawn(move || write_events(receiver));
+        EventStream {
+            sender,
+            writer: Mutex::new(Some(writer)),
+            sent: AtomicU64::new(0),
+            blocked: AtomicU64::new(0),
+            blocked_micros: AtomicU64::new(0),
+        }
+    }
+
+    /// 放入一条事件，队列满时阻塞到写出线程腾出空间
+    pub fn send(&self, line: String) {
+        self.sent.fetch_add(1, Ordering::Relaxed);
+        match self.sender.try_send(Some(line)) {
+            Ok(()) | Err(TrySendError::Disconnected(_)) => {}
+            Err(TrySendError::Full(line)) => {
+                let start = Instant::now();
+                let send = || {
+                    let _ = self.sender.send(line);
+                };
+                // 多线程运行时中先把当前线程上的其他任务转移走，避免它们跟着一起停顿
+                match Handle::try_current() {
+                    Ok(handle) if handle.runtime_flavor() == RuntimeFlavor::MultiThread => tokio::task::block_in_place(send),
+                    _ => send(),
+                }
+                self.blocked.fetch_add(1, Ordering::Relaxed);
+                self.blocked_micros.fetch_add(start.elapsed().as_micros() as u64, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// 等待已放入的事件全部写出并停止写出线程
+    pub fn finish(&self) -> EventStreamStats {
+        let _ = self.sender.send(None);
+        if let Some(writer) = self.writer.lock().ok().and_then(|mut writer| writer.take()) {
+            let _ = writer.join();
+        }
+        EventStreamStats {
+            sent: self.sent.load(Ordering::Relaxed),
+            blocked: self.blocked.load(Ordering::Relaxed),
+            blocked_time: Duration::from_micros(self.blocked_micros.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+/// 写出线程：每次取出当前积压的所有事件合并写出后 flush，下游仍能实时读到事件。
+/// 标准输出只在写出一批事件期间加锁，等待事件时不持有锁，任务的 println! (例如 --verbose) 不会被阻塞
+fn write_events(receiver: Receiver<Option<String>>) {
+    while let Ok(Some(line)) = receiver.recv() {
+        let mut stdout = io::BufWriter::new(io::stdout().lock());
+        // 下游关闭管道时丢弃剩余事件，但继续接收，避免发送方一直阻塞
+        let _ = writeln!(stdout, "{}", line);
+        loop {
+            match receiver.try_recv() {
+                Ok(Some(line)) => {
+                    let _ = writeln!(stdout, "{}", line);
+                }
+                Ok(None) | Err(TryRecvError::Disconnected) => {
+                    let _ = stdout.flush();
+                    return;
+                }
+                Err(TryRecvError::Empty) => break,
+            }
+        }
+        let _ = stdout.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn idle_writer_does_not_hold_stdout() {
+        let stream = EventStream::start(4);
+        stream.send("{\"event\":\"test\"}".to_string());
+        // 写出线程写完后等待下一条事件，此时其他线程必须能拿到标准输出的锁
+        let (done, locked) = mpsc::channel();
+        std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(50));
+            let _ = writeln!(io::stdout().lock());
+            let _ = done.send(());
+        });
+        assert!(locked.recv_timeout(Duration::from_secs(5)).is_ok());
+        assert_eq!(stream.finish().sent, 1);
+    }
+}
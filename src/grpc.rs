@@ -0,0 +1,246 @@
+// src/grpc.rs
+
+//! gRPC 一元调用 (unary call) 的压测客户端。
+//!
+//! 运行时用 protox 编译 --proto 指定的 .proto 文件 (以文件所在目录为 import 路径)，按 --service 和
+//! --grpc-method 找到要调用的方法，再把 -d 给出的 JSON 按方法的输入类型转换为 protobuf 消息，例如：
+//!
+//! ```text
+//! -m GRPC -u http://localhost:50051 --proto helloworld.proto --service helloworld.Greeter --grpc-method SayHello -d '{"name": "world"}'
+//! ```
+//!
+//! 请求消息在压测开始前编码一次，之后每次调用直接发送编码结果；响应按方法的输出类型解码。
+//! URL 只决定连接的地址，http:// 使用 h2c (HTTP/2 明文)，https:// 使用 TLS (系统根证书) 并通过 ALPN 协商 HTTP/2。
+//! 所有调用复用同一个 HTTP/2 连接上的多个流。
+
+use bytes::{Buf, BufMut, Bytes};
+use prost::Message;
+use prost_reflect::{DescriptorPool, DynamicMessage, MessageDescriptor};
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::Duration;
+use tonic::codec::{Codec, DecodeBuf, Decoder, EncodeBuf, Encoder};
+use tonic::metadata::{MetadataKey, MetadataValue};
+use tonic::transport::{Channel, ClientTlsConfig, Endpoint};
+use tonic::Status;
+
+/// gRPC 状态码名称，下标即状态码
+const STATUS_NAMES: [&str; 17] = [
+    "OK",
+    "CANCELLED",
+    "UNKNOWN",
+    "INVALID_ARGUMENT",
+    "DEADLINE_EXCEEDED",
+    "NOT_FOUND",
+    "ALREADY_EXISTS",
+    "PERMISSION_DENIED",
+    "RESOURCE_EXHAUSTED",
+    "FAILED_PRECONDITION",
+    "ABORTED",
+    "OUT_OF_RANGE",
+    "UNIMPLEMENTED",
+    "INTERNAL",
+    "UNAVAILABLE",
+    "DATA_LOSS",
+    "UNAUTHENTICATED",
+];
+
+/// 状态码对应的名称，未知状态码返回 None
+pub fn status_name(code: u32) -> Option<&'static str> {
+    STATUS_NAMES.get(code as usize).copied()
+}
+
+/// 一次调用的结果：调用完成并得到了 grpc-status
+pub struct GrpcResponse {
+    pub status: u32,
+    pub message: Option<String>, // grpc-message
+}
+
+/// 按 .proto 描述编码好的请求
+pub struct GrpcRequest {
+    path: http::uri::PathAndQuery, // /包名.服务名/方法名
+    message: Bytes,                // protobuf 编码的请求消息
+    response: MessageDescriptor,   // 方法的输出类型，用于解码响应
+}
+
+impl GrpcRequest {
+    /// 编译 proto 文件，找到 service 中的 method，并把 JSON 格式的 body 转换为方法的输入消息
+    /// (不指定时发送所有字段为默认值的消息)
+    pub fn load(proto: &Path, service: &str, method: &str, body: Option<&str>) -> Result<Self, String> {
+        let include = proto.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        let pool: DescriptorPool = protox::Compiler::new([include])
+            .and_then(|mut compiler| compiler.include_imports(true).open_file(proto).map(|compiler| compiler.descriptor_pool()))
+            .map_err(|e| format!("无法编译 proto 文件 {}: {}", proto.display(), e))?;
+        let service_descriptor = pool
+            .get_service_by_name(service)
+            .ok_or_else(|| format!("proto 文件 {} 中没有服务 {} (需要带包名，例如 helloworld.Greeter)", proto.display(), service))?;
+        let method_descriptor = service_descriptor
+            .methods()
+            .find(|candidate| candidate.name() == method)
+            .ok_or_else(|| format!("服务 {} 中没有方法 {}", service, method))?;
+        if method_descriptor.is_client_streaming() || method_descriptor.is_server_streaming() {
+            return Err(format!("{}/{} 是流式方法，gRPC 模式只支持一元调用", service, method));
+        }
+
+        let input = method_descriptor.input();
+        let message = match body {
+            Some(json) => {
+                let mut deserializer = serde_json::Deserializer::from_str(json);
+                DynamicMessage::deserialize(input.clone(), &mut deserializer)
+                    .and_then(|message| deserializer.end().map(|_| message))
+                    .map_err(|e| format!("请求体无法转换为 {}: {}", input.full_name(), e))?
+            }
+            None => DynamicMessage::new(input),
+        };
+        let path = format!("/{}/{}", service, method)
+            .parse()
+            .map_err(|e| format!("无效的 gRPC 方法路径: {}", e))?;
+        Ok(GrpcRequest {
+            path,
+            message: Bytes::from(message.encode_to_vec()),
+            response: method_descriptor.output(),
+        })
+    }
+}
+
+/// 发送预先编码的请求消息，按方法的输出类型解码响应
+struct DynamicCodec {
+    response: MessageDescriptor,
+}
+
+impl Codec for DynamicCodec {
+    type Encode = Bytes;
+    type Decode = DynamicMessage;
+    type Encoder = EncodedMessage;
+    type Decoder = DynamicDecoder;
+
+    fn encoder(&mut self) -> Self::Encoder {
+        EncodedMessage
+    }
+
+    fn decoder(&mut self) -> Self::Decoder {
+        DynamicDecoder(self.response.clone())
+    }
+}
+
+struct EncodedMessage;
+
+impl Encoder for EncodedMessage {
+    type Item = Bytes;
+    type Error = Status;
+
+    fn encode(&mut self, item: Bytes, dst: &mut EncodeBuf<'_>) -> Result<(), Status> {
+        dst.put(item);
+        Ok(())
+    }
+}
+
+struct DynamicDecoder(MessageDescriptor);
+
+impl Decoder for DynamicDecoder {
+    type Item = DynamicMessage;
+    type Error = Status;
+
+    fn decode(&mut self, src: &mut DecodeBuf<'_>) -> Result<Option<DynamicMessage>, Status> {
+        let message = DynamicMessage::decode(self.0.clone(), src.copy_to_bytes(src.remaining()))
+            .map_err(|e| Status::internal(format!("响应无法解码为 {}: {}", self.0.full_name(), e)))?;
+        Ok(Some(message))
+    }
+}
+
+/// 所有任务共享的 gRPC 客户端
+pub struct GrpcClient {
+    channel: Channel,
+    request: GrpcRequest,
+    timeout: Duration,
+}
+
+impl GrpcClient {
+    /// 创建连接到 url 的客户端，连接在第一次调用时建立
+    pub fn new(url: &str, request: GrpcRequest, timeout: Duration, user_agent: &str) -> Result<Self, String> {
+        let parsed = url::Url::parse(url).map_err(|e| format!("无效的 gRPC 地址 {}: {}", url, e))?;
+        let mut endpoint = Endpoint::from_shared(parsed.origin().ascii_serialization())
+            .and_then(|endpoint| endpoint.user_agent(user_agent))
+            .map_err(|e| format!("无效的 gRPC 地址 {}: {}", url, e))?
+            .tcp_nodelay(true);
+        if parsed.scheme() == "https" {
+            endpoint = endpoint
+                .tls_config(ClientTlsConfig::new().with_native_roots())
+                .map_err(|e| format!("无法初始化 TLS: {}", e))?;
+        }
+        Ok(GrpcClient {
+            channel: endpoint.connect_lazy(),
+            request,
+            timeout,
+        })
+    }
+
+    /// 发起一次一元调用，headers 作为请求元数据 (metadata) 发送，User-Agent 已在创建客户端时设置
+    pub async fn call(&self, headers: &HashMap<String, String>) -> Result<GrpcResponse, String> {
+        let mut request = tonic::Request::new(self.request.message.clone());
+        request.set_timeout(self.timeout);
+        for (key, value) in headers.iter().filter(|(key, _)| !key.eq_ignore_ascii_case("User-Agent")) {
+            let key = MetadataKey::from_bytes(key.to_ascii_lowercase().as_bytes()).map_err(|e| format!("无效的元数据名 {}: {}", key, e))?;
+            let value = MetadataValue::try_from(value.as_str()).map_err(|e| format!("无效的元数据值 {}: {}", value, e))?;
+            request.metadata_mut().insert(key, value);
+        }
+        let codec = DynamicCodec { response: self.request.response.clone() };
+
+        let exchange = async {
+            let mut client = tonic::client::Grpc::new(self.channel.clone());
+            client.ready().await.map_err(|e| format!("连接失败: {}", e))?;
+            match client.unary(request, self.request.path.clone(), codec).await {
+                Ok(_) => Ok(GrpcResponse { status: 0, message: None }),
+                Err(status) => Ok(GrpcResponse {
+                    status: status.code() as u32,
+                    message: (!status.message().is_empty()).then(|| status.message().to_string()),
+                }),
+            }
+        };
+        match tokio::time::timeout(self.timeout, exchange).await {
+            Ok(result) => result,
+            Err(_) => Err("读取超时 (Read-Timeout)".to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_body_is_encoded_with_the_method_input_type() {
+        let dir = std::env::temp_dir().join(format!("ab-grpc-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("types.proto"),
+            "syntax = \"proto3\";\npackage test;\nmessage Inner { int64 id = 1; }\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("echo.proto"),
+            "syntax = \"proto3\";\npackage test;\nimport \"types.proto\";\n\
+             message EchoRequest { string name = 1; repeated int32 counts = 2; Inner inner = 3; }\n\
+             message EchoReply { string name = 1; }\n\
+             service Echo { rpc Say(EchoRequest) returns (EchoReply); rpc Watch(EchoRequest) returns (stream EchoReply); }\n",
+        )
+        .unwrap();
+        let proto = dir.join("echo.proto");
+
+        let request = GrpcRequest::load(&proto, "test.Echo", "Say", Some(r#"{"name": "ab", "counts": [1, 2], "inner": {"id": "7"}}"#)).unwrap();
+        assert_eq!(request.path.as_str(), "/test.Echo/Say");
+        assert_eq!(request.response.full_name(), "test.EchoReply");
+        // name = "ab" (字段 1)，counts 按 proto3 默认打包编码 (字段 2)，inner.id = 7 (字段 3 内的字段 1)
+        assert_eq!(request.message.as_ref(), [0x0a, 0x02, b'a', b'b', 0x12, 0x02, 0x01, 0x02, 0x1a, 0x02, 0x08, 0x07]);
+
+        let empty = GrpcRequest::load(&proto, "test.Echo", "Say", None).unwrap();
+        assert!(empty.message.is_empty());
+
+        assert!(GrpcRequest::load(&proto, "test.Echo", "Say", Some(r#"{"unknown": 1}"#)).is_err());
+        assert!(GrpcRequest::load(&proto, "test.Echo", "Say", Some(r#"{"name": "ab"} x"#)).is_err());
+        assert!(GrpcRequest::load(&proto, "test.Echo", "Watch", None).is_err());
+        assert!(GrpcRequest::load(&proto, "Echo", "Say", None).is_err());
+        assert!(GrpcRequest::load(&proto, "test.Echo", "Missing", None).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
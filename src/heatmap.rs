@@ -0,0 +1,126 @@
+// src/heatmap.rs
+
+//! --heatmap-out：按完成时间把成功请求划分到固定长度的时间片，每个时间片保存一个延迟直方图，
+//! 压测结束后按统一的延迟区间写出 CSV，用于绘制 "时间 - 延迟" 热力图，观察长时间压测中延迟逐渐升高、
+//! GC 造成的锯齿、缓存预热等被整体百分位数掩盖的变化。文件每行一个单元格：
+//!
+//! ```text
+//! slice_start_s,latency_low_ms,latency_high_ms,count
+//! 0,0,1,0
+//! 0,1,2,153
+//! 0,2,4,87
+//! 5,0,1,0
+//! ```
+//!
+//! 延迟区间按 2 的幂划分 ([0,1)、[1,2)、[2,4) ...)，所有时间片使用相同的区间，直到全部请求中的最大延迟；
+//! 没有请求的单元格也会输出 (计数为 0)，以便直接透视成矩阵。
+
+use hdrhistogram::Histogram;
+use std::io::Write;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// 一个时间片的起始时间 (秒) 和各延迟区间 ((下限, 上限) 毫秒) 的请求数
+pub type SliceCounts = (f64, Vec<((u64, u64), u64)>);
+
+/// 每个时间片的延迟直方图，各并发任务分别记录，结束时合并
+pub struct Heatmap {
+    start: Instant,
+    interval: Duration,
+    sigfigs: u8,
+    slices: Vec<Histogram<u64>>, // 下标为时间片序号
+}
+
+impl Heatmap {
+    pub fn new(start: Instant, interval: Duration, sigfigs: u8) -> Self {
+        Heatmap { start, interval, sigfigs, slices: Vec::new() }
+    }
+
+    /// 记录一个在 completed 时刻完成的请求的延迟 (毫秒)
+    pub fn record(&mut self, completed: Instant, latency_ms: u64) {
+        let slice = (completed.saturating_duration_since(self.start).as_secs_f64() / self.interval.as_secs_f64()) as usize;
+        self.slice(slice).record(latency_ms.max(1)).unwrap();
+    }
+
+    /// 合并另一个任务的记录
+    pub fn merge(&mut self, other: &Heatmap) {
+        for (i, histogram) in other.slices.iter().enumerate() {
+            self.slice(i).add(histogram).unwrap();
+        }
+    }
+
+    /// 第 i 个时间片的直方图 (按需创建)
+    fn slice(&mut self, i: usize) -> &mut Histogram<u64> {
+        while self.slices.len() <= i {
+            self.slices.push(Histogram::<u64>::new(self.sigfigs).unwrap());
+        }
+        &mut self.slices[i]
+    }
+
+    /// 所有时间片共用的延迟区间 (毫秒，左闭右开)，覆盖到最大延迟
+    fn buckets(&self) -> Vec<(u64, u64)> {
+        let max = self.slices.iter().map(Histogram::max).max().unwrap_or(0);
+        let mut buckets = vec![(0, 1)];
+        while buckets.last().is_some_and(|&(_, high)| high <= max) {
+            let high = buckets.last().map_or(1, |&(_, high)| high);
+            buckets.push((high, high * 2));
+        }
+        buckets
+    }
+
+    /// 写出 CSV，返回写入的时间片数 (到最后一个有请求的时间片为止)
+    pub fn write(&self, path: &Path) -> std::io::Result<usize> {
+        let mut out = std::io::BufWriter::new(std::fs::File::create(path)?);
+        writeln!(out, "slice_start_s,latency_low_ms,latency_high_ms,count")?;
+        for (slice_start, counts) in self.cells() {
+            for ((low, high), count) in counts {
+                writeln!(out, "{},{},{},{}", slice_start, low, high, count)?;
+            }
+        }
+        out.flush()?;
+        Ok(self.slices.len())
+    }
+
+    /// 每个时间片的起始时间 (秒) 和各延迟区间的请求数
+    pub fn cells(&self) -> Vec<SliceCounts> {
+        let buckets = self.buckets();
+        self.slices
+            .iter()
+            .enumerate()
+            .map(|(i, histogram)| {
+                let counts = buckets
+                    .iter()
+                    .map(|&(low, high)| {
+                        let count = if histogram.is_empty() { 0 } else { histogram.count_between(low, high - 1) };
+                        ((low, high), count)
+                    })
+                    .collect();
+                (i as f64 * self.interval.as_secs_f64(), counts)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn heatmap_buckets_latency_per_slice() {
+        let start = Instant::now();
+        let mut first = Heatmap::new(start, Duration::from_secs(5), 3);
+        first.record(start + Duration::from_secs(1), 3);
+        first.record(start + Duration::from_secs(2), 1);
+        let mut second = Heatmap::new(start, Duration::from_secs(5), 3);
+        second.record(start + Duration::from_secs(11), 9);
+        first.merge(&second);
+
+        let cells = first.cells();
+        assert_eq!(cells.len(), 3);
+        assert_eq!(cells[0].0, 0.0);
+        assert_eq!(cells[0].1, vec![((0, 1), 0), ((1, 2), 1), ((2, 4), 1), ((4, 8), 0), ((8, 16), 0)]);
+        assert!(cells[1].1.iter().all(|&(_, count)| count == 0));
+        assert_eq!(cells[2].0, 10.0);
+        assert_eq!(cells[2].1[4], ((8, 16), 1));
+    }
+}
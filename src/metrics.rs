@@ -0,0 +1,279 @@
+// src/metrics.rs
+
+//! 压测进行中的实时指标：向 StatsD 的周期性上报，以及供 Prometheus 抓取的 `/metrics` 端点。
+//!
+//! 各任务完成请求时调用 [`LiveMetrics::record`] 累加计数并记录到当前窗口的直方图；
+//! StatsD 上报任务每个周期取出窗口直方图 (同时换上一个空的)，因此 P50/P99 只反映最近一个周期内的请求。
+//! Prometheus 端点输出的是从开始到现在的累计值，延迟按固定的桶边界统计。
+//! 实时指标包含预热阶段的请求，最终报告中的统计不受影响。
+//!
+//! 压测结束后还可以把最终的汇总以 OpenMetrics 文本格式推送到 Pushgateway (--pushgateway)，见 [`render_openmetrics_summary`]。
+
+use hdrhistogram::Histogram;
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream, UdpSocket};
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+
+/// Prometheus 延迟直方图的桶上界 (秒)，与 Prometheus 客户端库的默认值相同
+const LATENCY_BUCKETS: [f64; 11] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+/// 所有任务共享的实时指标
+pub struct LiveMetrics {
+    successful: AtomicU64,
+    failed: AtomicU64,
+    window: Mutex<Histogram<u64>>, // 当前周期内所有请求的延迟 (毫秒)
+    // 累计的延迟分布：每个桶只统计落在 (上一个上界, 本上界] 内的请求，最后一个元素对应 +Inf
+    bucket_counts: [AtomicU64; LATENCY_BUCKETS.len() + 1],
+    latency_sum_micros: AtomicU64,
+}
+
+impl LiveMetrics {
+    pub fn new() -> Self {
+        LiveMetrics {
+            successful: AtomicU64::new(0),
+            failed: AtomicU64::new(0),
+            window: Mutex::new(Histogram::<u64>::new(3).unwrap()),
+            bucket_counts: std::array::from_fn(|_| AtomicU64::new(0)),
+            latency_sum_micros: AtomicU64::new(0),
+        }
+    }
+
+    /// 记录一个完成的请求
+    pub fn record(&self, success: bool, latency: Duration) {
+        let counter = if success { &self.successful } else { &self.failed };
+        counter.fetch_add(1, Ordering::Relaxed);
+        if let Ok(mut window) = self.window.lock() {
+            let _ = window.record(latency.as_millis() as u64);
+        }
+        let seconds = latency.as_secs_f64();
+        let bucket = LATENCY_BUCKETS
+            .iter()
+            .position(|&upper| seconds <= upper)
+            .unwrap_or(LATENCY_BUCKETS.len());
+        self.bucket_counts[bucket].fetch_add(1, Ordering::Relaxed);
+        self.latency_sum_micros.fetch_add(latency.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    /// 以 Prometheus 文本格式输出累计指标
+    fn render_prometheus(&self) -> String {
+        let mut out = String::new();
+        out.push_str("# HELP ab_requests_total 已完成的请求数\n");
+        out.push_str("# TYPE ab_requests_total counter\n");
+        out.push_str(&format!("ab_requests_total{{outcome=\"success\"}} {}\n", self.successful.load(Ordering::Relaxed)));
+        out.push_str(&format!("ab_requests_total{{outcome=\"failure\"}} {}\n", self.failed.load(Ordering::Relaxed)));
+
+        out.push_str("# HELP ab_request_duration_seconds 请求延迟 (秒)，包括失败的请求\n");
+        out.push_str("# TYPE ab_request_duration_seconds histogram\n");
+        let mut cumulative = 0;
+        for (i, count) in self.bucket_counts.iter().enumerate() {
+            cumulative += count.load(Ordering::Relaxed);
+            let upper = LATENCY_BUCKETS.get(i).map_or("+Inf".to_string(), |upper| upper.to_string());
+            out.push_str(&format!("ab_request_duration_seconds_bucket{{le=\"{}\"}} {}\n", upper, cumulative));
+        }
+        let sum = self.latency_sum_micros.load(Ordering::Relaxed) as f64 / 1_000_000.0;
+        out.push_str(&format!("ab_request_duration_seconds_sum {}\n", sum));
+        out.push_str(&format!("ab_request_duration_seconds_count {}\n", cumulative));
+        out
+    }
+
+    /// 取出当前窗口的直方图并开始新的窗口
+    fn take_window(&self) -> Histogram<u64> {
+        match self.window.lock() {
+            Ok(mut window) => {
+                let empty = Histogram::<u64>::new_from(&window);
+                std::mem::replace(&mut *window, empty)
+            }
+            Err(_) => Histogram::<u64>::new(3).unwrap(),
+        }
+    }
+}
+
+/// 周期性地将实时指标以 StatsD 行格式 (`name:value|type`) 通过 UDP 发送
+pub struct StatsdReporter {
+    stop: oneshot::Sender<()>,
+    handle: JoinHandle<()>,
+}
+
+impl StatsdReporter {
+    /// 连接 StatsD 服务器 (host:port) 并启动上报任务，指标名以 prefix 开头
+    pub async fn start(
+        address: &str,
+        prefix: String,
+        interval: Duration,
+        metrics: Arc<LiveMetrics>,
+    ) -> io::Result<Self> {
+        let target = tokio::net::lookup_host(address)
+            .await?
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("无法解析 StatsD 地址: {}", address)))?;
+        // 本地地址与解析出的目标地址族一致，IPv6 目标 (例如 [::1]:8125) 不能从 IPv4 套接字发送
+        let local = if target.is_ipv6() { "[::]:0" } else { "0.0.0.0:0" };
+        let socket = UdpSocket::bind(local).await?;
+        socket.connect(target).await?;
+        let (stop, mut stopped) = oneshot::channel();
+        let handle = tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.tick().await; // 第一次 tick 立即完成
+            let mut last = Snapshot { at: Instant::now(), successful: 0, failed: 0 };
+            loop {
+                let finished = tokio::select! {
+                    _ = ticker.tick() => false,
+                    _ = &mut stopped => true,
+                };
+                let payload = last.advance(&prefix, &metrics);
+                // UDP 上报尽力而为，StatsD 不可达时忽略发送错误
+                let _ = socket.send(payload.as_bytes()).await;
+                if finished {
+                    break;
+                }
+            }
+        });
+        Ok(StatsdReporter { stop, handle })
+    }
+
+    /// 发送最后一个周期的指标并停止上报
+    pub async fn finish(self) {
+        let _ = self.stop.send(());
+        let _ = self.handle.await;
+    }
+}
+
+/// 上一次上报时的累计值，用于计算本周期的增量
+struct Snapshot {
+    at: Instant,
+    successful: u64,
+    failed: u64,
+}
+
+impl Snapshot {
+    /// 生成本周期的 StatsD 数据包 (多个指标以换行分隔) 并更新快照
+    fn advance(&mut self, prefix: &str, metrics: &LiveMetrics) -> String {
+        let now = Instant::now();
+        let successful = metrics.successful.load(Ordering::Relaxed);
+        let failed = metrics.failed.load(Ordering::Relaxed);
+        let window = metrics.take_window();
+        let new_successful = successful - self.successful;
+        let new_failed = failed - self.failed;
+        let elapsed = (now - self.at).as_secs_f64().max(f64::EPSILON);
+        let rps = (new_successful + new_failed) as f64 / elapsed;
+        *self = Snapshot { at: now, successful, failed };
+
+        let mut lines = vec![
+            format!("{}.rps:{:.2}|g", prefix, rps),
+            format!("{}.requests.success:{}|c", prefix, new_successful),
+            format!("{}.requests.failure:{}|c", prefix, new_failed),
+        ];
+        if !window.is_empty() {
+            lines.push(format!("{}.latency.p50:{}|g", prefix, window.value_at_percentile(50.0)));
+            lines.push(format!("{}.latency.p99:{}|g", prefix, window.value_at_percentile(99.0)));
+        }
+        lines.join("\n")
+    }
+}
+
+/// 以 OpenMetrics 文本格式输出压测结束时的汇总：请求数、延迟直方图 (与实时指标使用相同的桶边界) 和运行时长。
+/// latency_ms 是所有请求 (包括失败的请求) 的延迟 (毫秒)，不含预热阶段
+pub fn render_openmetrics_summary(successful: usize, failed: usize, latency_ms: &Histogram<u64>, duration_secs: f64) -> String {
+    let mut out = String::new();
+    out.push_str("# TYPE ab_requests counter\n");
+    out.push_str("# HELP ab_requests 已完成的请求数\n");
+    out.push_str(&format!("ab_requests_total{{outcome=\"success\"}} {}\n", successful));
+    out.push_str(&format!("ab_requests_total{{outcome=\"failure\"}} {}\n", failed));
+
+    out.push_str("# TYPE ab_request_duration_seconds histogram\n");
+    out.push_str("# HELP ab_request_duration_seconds 请求延迟 (秒)，包括失败的请求\n");
+    let mut bucket_counts = [0u64; LATENCY_BUCKETS.len() + 1];
+    let mut sum_ms = 0u64;
+    for value in latency_ms.iter_recorded() {
+        let ms = value.value_iterated_to();
+        let bucket = LATENCY_BUCKETS
+            .iter()
+            .position(|&upper| ms as f64 / 1000.0 <= upper)
+            .unwrap_or(LATENCY_BUCKETS.len());
+        bucket_counts[bucket] += value.count_at_value();
+        sum_ms += ms * value.count_at_value();
+    }
+    let mut cumulative = 0;
+    for (i, count) in bucket_counts.iter().enumerate() {
+        cumulative += count;
+        // OpenMetrics 要求 le 使用规范的浮点数表示 (例如 1.0 而不是 1)
+        let upper = LATENCY_BUCKETS.get(i).map_or("+Inf".to_string(), |upper| format!("{:?}", upper));
+        out.push_str(&format!("ab_request_duration_seconds_bucket{{le=\"{}\"}} {}\n", upper, cumulative));
+    }
+    out.push_str(&format!("ab_request_duration_seconds_sum {}\n", sum_ms as f64 / 1000.0));
+    out.push_str(&format!("ab_request_duration_seconds_count {}\n", cumulative));
+
+    out.push_str("# TYPE ab_run_duration_seconds gauge\n");
+    out.push_str("# HELP ab_run_duration_seconds 压测的统计时长 (秒)，不含预热阶段\n");
+    out.push_str(&format!("ab_run_duration_seconds {}\n", duration_secs));
+    out.push_str("# EOF\n");
+    out
+}
+
+/// Prometheus 端点接受连接失败后的初始等待时间，连续失败时翻倍
+const ACCEPT_BACKOFF_MIN: Duration = Duration::from_millis(10);
+/// 接受连接失败后的最长等待时间
+const ACCEPT_BACKOFF_MAX: Duration = Duration::from_secs(1);
+
+/// 在 0.0.0.0:port 上启动 Prometheus 抓取端点，GET /metrics 返回当前的累计指标。
+/// 端口绑定失败时返回错误；服务在进程退出前一直运行
+pub async fn serve_prometheus(port: u16, metrics: Arc<LiveMetrics>) -> io::Result<()> {
+    let listener = TcpListener::bind(("0.0.0.0", port)).await?;
+    tokio::spawn(async move {
+        let mut backoff = ACCEPT_BACKOFF_MIN;
+        loop {
+            let stream = match listener.accept().await {
+                Ok((stream, _)) => stream,
+                Err(e) => {
+                    // 文件描述符耗尽等错误通常会持续一段时间，等待后再重试，避免空转占满 CPU
+                    eprintln!("Prometheus 端点接受连接失败: {}，{} ms 后重试", e, backoff.as_millis());
+                    tokio::time::sleep(backoff).await;
+                    backoff = (backoff * 2).min(ACCEPT_BACKOFF_MAX);
+                    continue;
+                }
+            };
+            backoff = ACCEPT_BACKOFF_MIN;
+            let metrics = metrics.clone();
+            tokio::spawn(async move {
+                let _ = handle_scrape(stream, &metrics).await;
+            });
+        }
+    });
+    Ok(())
+}
+
+/// 处理一次抓取请求：读取请求头部后按请求行分派，响应后关闭连接
+async fn handle_scrape(mut stream: TcpStream, metrics: &LiveMetrics) -> io::Result<()> {
+    let mut head = Vec::new();
+    let mut buf = [0u8; 1024];
+    while !head.windows(4).any(|w| w == b"\r\n\r\n") && head.len() < 8192 {
+        let read = stream.read(&mut buf).await?;
+        if read == 0 {
+            break;
+        }
+        head.extend_from_slice(&buf[..read]);
+    }
+    let head = String::from_utf8_lossy(&head);
+    let mut request_line = head.lines().next().unwrap_or_default().split_whitespace();
+    let (method, path) = (request_line.next().unwrap_or_default(), request_line.next().unwrap_or_default());
+
+    let (status, content_type, body) = if method == "GET" && path.split('?').next() == Some("/metrics") {
+        ("200 OK", "text/plain; version=0.0.4; charset=utf-8", metrics.render_prometheus())
+    } else {
+        ("404 Not Found", "text/plain; charset=utf-8", "not found\n".to_string())
+    };
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body,
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
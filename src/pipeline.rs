@@ -0,0 +1,268 @@
+// src/pipeline.rs
+
+//! HTTP/1.1 管线化 (pipelining) 压测使用的底层连接。
+//!
+//! reqwest (hyper) 的客户端不支持管线化，这里直接在 TCP 连接上读写 HTTP/1.1 报文：
+//! 先连续写出一批请求，再按顺序逐个读取响应。只支持 http:// (不支持 TLS)，
+//! 响应体只读取并丢弃，不做解压。
+
+use std::io;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use url::Url;
+
+/// 允许管线化的幂等方法 (RFC 9112 建议只对幂等请求使用管线化)
+pub const IDEMPOTENT_METHODS: &[&str] = &["GET", "HEAD", "OPTIONS", "PUT", "DELETE", "TRACE"];
+
+/// 响应头部的最大长度，超过时视为协议错误
+const MAX_HEAD_LEN: usize = 64 * 1024;
+
+/// 读取到的单个响应
+#[derive(Debug)]
+pub struct RawResponse {
+    pub status: u16,
+    pub keep_alive: bool, // 服务端是否允许继续在该连接上发送请求
+}
+
+/// 一个用于管线化请求的 HTTP/1.1 连接
+pub struct PipelinedConnection {
+    reader: BufReader<TcpStream>,
+}
+
+impl PipelinedConnection {
+    /// 建立到 URL 所在主机的 TCP 连接
+    pub async fn connect(url: &Url) -> io::Result<Self> {
+        let host = url
+            .host_str()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "URL 中缺少主机名"))?;
+        let port = url.port_or_known_default().unwrap_or(80);
+        let stream = TcpStream::connect((host, port)).await?;
+        stream.set_nodelay(true)?;
+        Ok(PipelinedConnection { reader: BufReader::new(stream) })
+    }
+
+    /// 一次性写出一批已编码的请求
+    pub async fn send(&mut self, requests: &[u8]) -> io::Result<()> {
+        let stream = self.reader.get_mut();
+        stream.write_all(requests).await?;
+        stream.flush().await
+    }
+
+    /// 读取下一个完整的响应 (跳过 1xx 临时响应)，head_request 为 true 时响应没有响应体
+    pub async fn read_response(&mut self, head_request: bool) -> io::Result<RawResponse> {
+        loop {
+            let head = self.read_head().await?;
+            let mut headers = [httparse::EMPTY_HEADER; 64];
+            let mut response = httparse::Response::new(&mut headers);
+            match response.parse(&head) {
+                Ok(httparse::Status::Complete(_)) => {}
+                Ok(httparse::Status::Partial) => return Err(invalid_data("响应头部不完整")),
+                Err(e) => return Err(invalid_data(format!("无效的响应头部: {}", e))),
+            }
+            let status = response.code.unwrap_or_default();
+            if (100..200).contains(&status) {
+                continue;
+            }
+
+            let mut content_length = None;
+            let mut chunked = false;
+            let mut connection_close = response.version == Some(0); // HTTP/1.0 默认不保持连接
+            for header in response.headers.iter() {
+                let value = String::from_utf8_lossy(header.value).trim().to_ascii_lowercase();
+                if header.name.eq_ignore_ascii_case("Content-Length") {
+                    content_length = Some(value.parse::<u64>().map_err(|_| invalid_data("无效的 Content-Length"))?);
+                } else if header.name.eq_ignore_ascii_case("Transfer-Encoding") {
+                    chunked = value.split(',').any(|coding| coding.trim() == "chunked");
+                } else if header.name.eq_ignore_ascii_case("Connection") {
+                    connection_close = value.split(',').any(|option| option.trim() == "close");
+                }
+            }
+
+            let no_body = head_request || status == 204 || status == 304;
+            let keep_alive = if no_body {
+                !connection_close
+            } else if chunked {
+                self.read_chunked_body().await?;
+                !connection_close
+            } else if let Some(length) = content_length {
+                self.discard(length).await?;
+                !connection_close
+            } else {
+                // 既没有 Content-Length 也不是 chunked，响应体到连接关闭为止
+                tokio::io::copy(&mut self.reader, &mut tokio::io::sink()).await?;
+                false
+            };
+            return Ok(RawResponse { status, keep_alive });
+        }
+    }
+
+    /// 读取响应头部 (到空行为止)
+    async fn read_head(&mut self) -> io::Result<Vec<u8>> {
+        let mut head = Vec::new();
+        loop {
+            let read = self.reader.read_until(b'\n', &mut head).await?;
+            if read == 0 {
+                return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "连接已被服务端关闭"));
+            }
+            if head.ends_with(b"\r\n\r\n") || head.ends_with(b"\n\n") {
+                return Ok(head);
+            }
+            if head.len() > MAX_HEAD_LEN {
+                return Err(invalid_data("响应头部过长"));
+            }
+        }
+    }
+
+    /// 读取并丢弃 chunked 编码的响应体
+    async fn read_chunked_body(&mut self) -> io::Result<()> {
+        let mut line = String::new();
+        loop {
+            line.clear();
+            self.reader.read_line(&mut line).await?;
+            let size_text = line.split(';').next().unwrap_or_default().trim();
+            let size = u64::from_str_radix(size_text, 16).map_err(|_| invalid_data("无效的 chunk 大小"))?;
+            if size == 0 {
+                // 跳过 trailer，直到空行
+                loop {
+                    line.clear();
+                    if self.reader.read_line(&mut line).await? == 0 || line.trim().is_empty() {
+                        return Ok(());
+                    }
+                }
+            }
+            self.discard(size).await?;
+            line.clear();
+            self.reader.read_line(&mut line).await?; // chunk 结尾的 CRLF
+        }
+    }
+
+    /// 读取并丢弃指定长度的数据
+    async fn discard(&mut self, length: u64) -> io::Result<()> {
+        let copied = tokio::io::copy(&mut (&mut self.reader).take(length), &mut tokio::io::sink()).await?;
+        if copied < length {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "响应体不完整"));
+        }
+        Ok(())
+    }
+}
+
+fn invalid_data(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+/// 将请求编码为 HTTP/1.1 报文。未指定时自动添加 Host，有请求体时添加 Content-Length
+pub fn encode_request<'a>(
+    method: &str,
+    url: &Url,
+    headers: impl IntoIterator<Item = (&'a str, &'a str)>,
+    body: Option<&[u8]>,
+) -> Vec<u8> {
+    let mut path = url.path().to_string();
+    if let Some(query) = url.query() {
+        path.push('?');
+        path.push_str(query);
+    }
+    let mut encoded = format!("{} {} HTTP/1.1\r\n", method, path);
+    let mut has_host = false;
+    for (key, value) in headers {
+        has_host |= key.eq_ignore_ascii_case("Host");
+        if key.eq_ignore_ascii_case("Content-Length") {
+            continue; // 以实际请求体长度为准
+        }
+        encoded.push_str(&format!("{}: {}\r\n", key, value));
+    }
+    if !has_host {
+        match (url.host_str(), url.port()) {
+            (Some(host), Some(port)) => encoded.push_str(&format!("Host: {}:{}\r\n", host, port)),
+            (Some(host), None) => encoded.push_str(&format!("Host: {}\r\n", host)),
+            (None, _) => {}
+        }
+    }
+    if let Some(body) = body {
+        encoded.push_str(&format!("Content-Length: {}\r\n", body.len()));
+    }
+    encoded.push_str("\r\n");
+    let mut bytes = encoded.into_bytes();
+    if let Some(body) = body {
+        bytes.extend_from_slice(body);
+    }
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tokio::net::TcpListener;
+
+    /// 启动只接受一个连接的服务端，按顺序写出各段数据 (每段之间稍作停顿，使客户端分多次读到)
+    async fn connect_to(parts: Vec<&'static [u8]>) -> PipelinedConnection {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = Url::parse(&format!("http://{}/", listener.local_addr().unwrap())).unwrap();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            for part in parts {
+                stream.write_all(part).await.unwrap();
+                stream.flush().await.unwrap();
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+        });
+        PipelinedConnection::connect(&url).await.unwrap()
+    }
+
+    #[test]
+    fn encode_request_adds_host_and_content_length() {
+        let url = Url::parse("http://example.com:8080/items?id=1").unwrap();
+        let headers = [("X-Token", "abc"), ("Content-Length", "999")];
+        let encoded = encode_request("PUT", &url, headers, Some(b"{\"a\":1}"));
+        assert_eq!(
+            String::from_utf8(encoded).unwrap(),
+            "PUT /items?id=1 HTTP/1.1\r\nX-Token: abc\r\nHost: example.com:8080\r\nContent-Length: 7\r\n\r\n{\"a\":1}"
+        );
+    }
+
+    #[test]
+    fn encode_request_keeps_explicit_host_without_body() {
+        let url = Url::parse("http://example.com/").unwrap();
+        let encoded = encode_request("GET", &url, [("host", "backend.internal")], None);
+        assert_eq!(String::from_utf8(encoded).unwrap(), "GET / HTTP/1.1\r\nhost: backend.internal\r\n\r\n");
+    }
+
+    #[tokio::test]
+    async fn chunked_body_skips_extensions_and_trailers() {
+        let mut connection = connect_to(vec![
+            b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
+              5;name=value\r\nhello\r\n6\r\n world\r\n0;last\r\nX-Checksum: 1234\r\nX-Other: a\r\n\r\n\
+              HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+        ])
+        .await;
+        let first = connection.read_response(false).await.unwrap();
+        assert_eq!((first.status, first.keep_alive), (200, true));
+        // trailer 读完后紧接着的是下一个响应
+        let second = connection.read_response(false).await.unwrap();
+        assert_eq!((second.status, second.keep_alive), (404, false));
+    }
+
+    #[tokio::test]
+    async fn chunked_body_split_across_reads() {
+        let mut connection = connect_to(vec![
+            b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\na\r\n0123",
+            b"45678",
+            b"9\r",
+            b"\n0\r\n",
+            b"\r\nHTTP/1.1 204 No Content\r\n\r\n",
+        ])
+        .await;
+        assert_eq!(connection.read_response(false).await.unwrap().status, 200);
+        assert_eq!(connection.read_response(false).await.unwrap().status, 204);
+    }
+
+    #[tokio::test]
+    async fn malformed_chunk_size_is_invalid_data() {
+        let mut connection =
+            connect_to(vec![b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\nzz\r\nhello\r\n0\r\n\r\n"]).await;
+        let error = connection.read_response(false).await.unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(error.to_string(), "无效的 chunk 大小");
+    }
+}
@@ -0,0 +1,115 @@
+// src/profile.rs
+
+//! --profile 负载阶段文件的解析。每行一个阶段，依次运行：
+//!
+//! ```text
+//! # 并发数 时长 (秒)
+//! 10 30
+//! 50 60
+//! 100 30
+//! ```
+//!
+//! 以 # 开头的行是注释，空行会被忽略。时长可以带 s 或 m 后缀 (例如 90s、2m)。
+
+use std::path::Path;
+use std::time::Duration;
+
+/// 负载曲线中的一个阶段
+#[derive(Debug, PartialEq)]
+pub struct ProfileStage {
+    pub concurrency: usize,
+    pub duration: Duration,
+}
+
+/// 读取并解析阶段文件
+pub fn load_profile(path: &Path) -> Result<Vec<ProfileStage>, String> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| format!("无法读取负载阶段文件 {}: {}", path.display(), e))?;
+    let stages = parse_profile(&content)?;
+    if stages.is_empty() {
+        return Err(format!("负载阶段文件 {} 中没有任何阶段", path.display()));
+    }
+    Ok(stages)
+}
+
+/// 解析阶段文件内容
+pub fn parse_profile(content: &str) -> Result<Vec<ProfileStage>, String> {
+    let mut stages = Vec::new();
+    for (i, raw_line) in content.lines().enumerate() {
+        let line_no = i + 1;
+        let line = raw_line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let [concurrency, duration] = fields.as_slice() else {
+            return Err(format!("负载阶段文件第 {} 行: 格式应为 \"<并发数> <时长>\"", line_no));
+        };
+        let concurrency = concurrency
+            .parse::<usize>()
+            .ok()
+            .filter(|&concurrency| concurrency > 0)
+            .ok_or_else(|| format!("负载阶段文件第 {} 行: 无效的并发数 \"{}\"", line_no, concurrency))?;
+        let duration = parse_duration(duration)
+            .filter(|duration| !duration.is_zero())
+            .ok_or_else(|| format!("负载阶段文件第 {} 行: 无效的时长 \"{}\"", line_no, duration))?;
+        stages.push(ProfileStage { concurrency, duration });
+    }
+    Ok(stages)
+}
+
+/// 解析时长：不带后缀或带 s 后缀为秒，m 后缀为分钟
+fn parse_duration(value: &str) -> Option<Duration> {
+    let (number, unit_secs) = match value.strip_suffix('m') {
+        Some(minutes) => (minutes, 60),
+        None => (value.strip_suffix('s').unwrap_or(value), 1),
+    };
+    number.parse::<u64>().ok().map(|n| Duration::from_secs(n * unit_secs))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_profile_reads_stages_and_skips_comments() {
+        let stages = parse_profile("# 并发数 时长\n10 30\n\n  50 90s  \n100 2m\n").unwrap();
+        assert_eq!(
+            stages,
+            [
+                ProfileStage { concurrency: 10, duration: Duration::from_secs(30) },
+                ProfileStage { concurrency: 50, duration: Duration::from_secs(90) },
+                ProfileStage { concurrency: 100, duration: Duration::from_secs(120) },
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_profile_rejects_invalid_durations() {
+        for duration in ["0", "0s", "1h", "-5", "1.5", "m", ""] {
+            let line = format!("10 {}\n", duration);
+            assert!(parse_profile(&line).is_err(), "{:?}", line);
+        }
+        assert_eq!(
+            parse_profile("10 30\n20 abc\n"),
+            Err("负载阶段文件第 2 行: 无效的时长 \"abc\"".to_string())
+        );
+    }
+
+    #[test]
+    fn parse_profile_rejects_malformed_lines() {
+        assert_eq!(parse_profile("0 30"), Err("负载阶段文件第 1 行: 无效的并发数 \"0\"".to_string()));
+        assert!(parse_profile("10").is_err());
+        assert!(parse_profile("10 30 5").is_err());
+    }
+
+    #[test]
+    fn empty_profiles_have_no_stages() {
+        assert_eq!(parse_profile(""), Ok(Vec::new()));
+        assert_eq!(parse_profile("# 只有注释\n\n"), Ok(Vec::new()));
+        let path = std::env::temp_dir().join(format!("ab-profile-test-{}.txt", std::process::id()));
+        std::fs::write(&path, "# 只有注释\n").unwrap();
+        assert!(load_profile(&path).unwrap_err().contains("没有任何阶段"));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
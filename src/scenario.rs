@@ -0,0 +1,142 @@
+// src/scenario.rs
+
+//! --scenario 场景文件的解析。
+//!
+//! 场景由若干命名的步骤组成，每个步骤有自己的请求、固定的并发任务数和可选的速率上限，
+//! 用于模拟由多股流量组成的真实负载，例如低速、稳定的后台写入与突发的读取同时进行：
+//!
+//! ```text
+//! {"steps": [
+//!   {"name": "audit", "method": "POST", "url": "http://localhost:8080/events", "body": {"type": "audit"}, "concurrency": 2, "rate": 5},
+//!   {"name": "browse", "url": "http://localhost:8080/items", "headers": {"X-Account-ID": "8675309"}, "concurrency": 50}
+//! ]}
+//! ```
+//!
+//! - name、url 和 concurrency 是必需的，name 不能重复，method 默认为 GET
+//! - body 为字符串时原样发送，为其他 JSON 值时发送其 JSON 文本
+//! - concurrency 为固定发送该步骤的并发任务数，rate 为该步骤的速率上限 (请求/秒，可以带小数)
+
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use url::Url;
+
+/// 场景中的单个步骤
+#[derive(Debug)]
+pub struct Step {
+    pub name: String,
+    pub method: String,
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Option<Vec<u8>>,
+    pub concurrency: usize, // 固定发送该步骤的并发任务数
+    pub rate: Option<f64>,  // 该步骤的速率上限 (请求/秒)
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct JsonScenario {
+    steps: Vec<JsonStep>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct JsonStep {
+    name: String,
+    #[serde(default = "default_method")]
+    method: String,
+    url: String,
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+    body: Option<serde_json::Value>,
+    concurrency: usize,
+    rate: Option<f64>,
+}
+
+fn default_method() -> String {
+    "GET".to_string()
+}
+
+/// 读取并解析场景文件
+pub fn load_scenario(path: &Path) -> Result<Vec<Step>, String> {
+    let content = fs::read_to_string(path).map_err(|e| format!("无法读取场景文件 {}: {}", path.display(), e))?;
+    parse_scenario(&content).map_err(|e| format!("场景文件 {}: {}", path.display(), e))
+}
+
+/// 解析场景文件内容
+pub fn parse_scenario(content: &str) -> Result<Vec<Step>, String> {
+    let scenario: JsonScenario = serde_json::from_str(content).map_err(|e| e.to_string())?;
+    if scenario.steps.is_empty() {
+        return Err("没有任何步骤".to_string());
+    }
+    let mut steps: Vec<Step> = Vec::with_capacity(scenario.steps.len());
+    for step in scenario.steps {
+        if step.name.trim().is_empty() {
+            return Err("步骤的 name 不能为空".to_string());
+        }
+        if steps.iter().any(|seen| seen.name == step.name) {
+            return Err(format!("步骤 {} 重复", step.name));
+        }
+        Url::parse(&step.url).map_err(|e| format!("步骤 {}: 无效的URL \"{}\": {}", step.name, step.url, e))?;
+        if step.concurrency == 0 {
+            return Err(format!("步骤 {}: concurrency 不能为 0", step.name));
+        }
+        if let Some(rate) = step.rate.filter(|rate| !(*rate > 0.0 && rate.is_finite())) {
+            return Err(format!("步骤 {}: 无效的速率 {} (应为大于 0 的请求数/秒)", step.name, rate));
+        }
+        steps.push(Step {
+            name: step.name,
+            method: step.method.to_uppercase(),
+            url: step.url,
+            headers: step.headers.into_iter().collect(),
+            body: step.body.map(|body| match body {
+                serde_json::Value::String(text) => text.into_bytes(),
+                other => other.to_string().into_bytes(),
+            }),
+            concurrency: step.concurrency,
+            rate: step.rate,
+        });
+    }
+    Ok(steps)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scenario_steps_keep_order_and_defaults() {
+        let steps = parse_scenario(
+            r#"{"steps": [
+                {"name": "audit", "method": "post", "url": "http://localhost/events", "body": {"type": "audit"}, "concurrency": 2, "rate": 0.5},
+                {"name": "browse", "url": "http://localhost/items", "headers": {"X-Account-ID": "1"}, "body": "raw", "concurrency": 3}
+            ]}"#,
+        )
+        .unwrap();
+        assert_eq!(steps.len(), 2);
+        assert_eq!((steps[0].name.as_str(), steps[0].method.as_str()), ("audit", "POST"));
+        assert_eq!(steps[0].body.as_deref(), Some(&b"{\"type\":\"audit\"}"[..]));
+        assert_eq!((steps[0].concurrency, steps[0].rate), (2, Some(0.5)));
+        assert_eq!(steps[1].method, "GET");
+        assert_eq!(steps[1].headers, vec![("X-Account-ID".to_string(), "1".to_string())]);
+        assert_eq!(steps[1].body.as_deref(), Some(&b"raw"[..]));
+        assert_eq!(steps[1].rate, None);
+    }
+
+    #[test]
+    fn invalid_scenarios_are_rejected() {
+        let step = |fields: &str| format!(r#"{{"steps": [{{"name": "a", "url": "http://localhost/", {}}}]}}"#, fields);
+        assert!(parse_scenario(&step(r#""concurrency": 1"#)).is_ok());
+        assert!(parse_scenario(r#"{"steps": []}"#).is_err());
+        assert!(parse_scenario(&step(r#""concurrency": 0"#)).is_err());
+        assert!(parse_scenario(&step(r#""concurrency": 1, "rate": 0"#)).is_err());
+        assert!(parse_scenario(&step(r#""rate": 5"#)).is_err()); // concurrency 是必需的
+        assert!(parse_scenario(&step(r#""concurrency": 1, "timeout": 5"#)).is_err());
+        assert!(parse_scenario(
+            r#"{"steps": [{"name": "a", "url": "http://localhost/", "concurrency": 1}, {"name": "a", "url": "http://localhost/", "concurrency": 1}]}"#,
+        )
+        .is_err());
+        assert!(parse_scenario(r#"{"steps": [{"name": "a", "url": "localhost", "concurrency": 1}]}"#).is_err());
+    }
+}
@@ -0,0 +1,203 @@
+// src/sqlite.rs
+
+//! --sqlite 结果数据库：每次压测结束后向 runs 表追加一行汇总，指定 --sqlite-requests 时还把每个请求写入 requests 表。
+//! 表结构在第一次使用时创建，之后的运行只追加数据，便于在 CI 中长期积累结果后直接用 SQL 查询趋势：
+//!
+//! ```sql
+//! SELECT timestamp, rps, p99_ms FROM runs WHERE config_hash = ? ORDER BY id;
+//! ```
+
+use rusqlite::{Connection, params};
+use std::path::Path;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS runs (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    timestamp TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%SZ', 'now')),
+    config_hash TEXT NOT NULL,
+    target TEXT NOT NULL,
+    concurrency INTEGER NOT NULL,
+    duration_secs REAL NOT NULL,
+    total_requests INTEGER NOT NULL,
+    successful_requests INTEGER NOT NULL,
+    failed_requests INTEGER NOT NULL,
+    rps REAL,
+    mean_ms REAL,
+    p50_ms REAL,
+    p90_ms REAL,
+    p95_ms REAL,
+    p99_ms REAL,
+    max_ms REAL,
+    config TEXT NOT NULL,
+    report TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS requests (
+    run_id INTEGER NOT NULL REFERENCES runs(id),
+    idx INTEGER NOT NULL,
+    timestamp_ms INTEGER NOT NULL,
+    duration_ms REAL NOT NULL,
+    success INTEGER NOT NULL,
+    status INTEGER,
+    error TEXT
+);
+CREATE INDEX IF NOT EXISTS requests_run_id ON requests(run_id);
+";
+
+/// 写入 runs 表的一次运行的汇总
+pub struct RunRow<'a> {
+    pub config_hash: String,
+    pub target: &'a str,
+    pub concurrency: usize,
+    pub duration_secs: f64,
+    pub total_requests: usize,
+    pub successful_requests: usize,
+    pub failed_requests: usize,
+    pub rps: Option<f64>,
+    pub latency: Option<[f64; 6]>, // 平均值、P50、P90、P95、P99、最大值 (毫秒)，没有成功请求时为 None
+    pub config: String, // 配置的 JSON
+    pub report: String, // 与 --output json 相同的结果
+}
+
+/// 写入 requests 表的单个请求
+pub struct RequestRow {
+    pub index: usize,
+    pub timestamp_ms: u64,
+    pub duration_ms: f64,
+    pub success: bool,
+    pub status: Option<u16>,
+    pub error: Option<String>,
+}
+
+/// 配置的哈希 (FNV-1a 64 位)，用于在数据库中筛选相同配置的历次运行。与 Rust 版本无关，结果稳定
+pub fn config_hash(config_json: &str) -> String {
+    format!("{:016x}", crate::fnv1a(config_json.as_bytes()))
+}
+
+/// 在一个事务中追加一次运行的汇总和请求明细，返回该运行在 runs 表中的 id
+pub fn append_run(path: &Path, run: &RunRow, requests: &[RequestRow]) -> Result<i64, String> {
+    let mut conn = Connection::open(path).map_err(|e| format!("无法打开数据库: {}", e))?;
+    conn.execute_batch(SCHEMA).map_err(|e| format!("无法创建表结构: {}", e))?;
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    let [mean, p50, p90, p95, p99, max] = run.latency.map_or([None; 6], |latency| latency.map(Some));
+    tx.execute(
+        "INSERT INTO runs (config_hash, target, concurrency, duration_secs, total_requests, successful_requests,
+            failed_requests, rps, mean_ms, p50_ms, p90_ms, p95_ms, p99_ms, max_ms, config, report)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)",
+        params![
+            run.config_hash,
+            run.target,
+            run.concurrency as i64,
+            run.duration_secs,
+            run.total_requests as i64,
+            run.successful_requests as i64,
+            run.failed_requests as i64,
+            run.rps,
+            mean,
+            p50,
+            p90,
+            p95,
+            p99,
+            max,
+            run.config,
+            run.report,
+        ],
+    )
+    .map_err(|e| format!("写入运行汇总失败: {}", e))?;
+    let run_id = tx.last_insert_rowid();
+    {
+        let mut insert = tx
+            .prepare(
+                "INSERT INTO requests (run_id, idx, timestamp_ms, duration_ms, success, status, error)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            )
+            .map_err(|e| e.to_string())?;
+        for request in requests {
+            insert
+                .execute(params![
+                    run_id,
+                    request.index as i64,
+                    request.timestamp_ms as i64,
+                    request.duration_ms,
+                    request.success,
+                    request.status,
+                    request.error,
+                ])
+                .map_err(|e| format!("写入请求明细失败: {}", e))?;
+        }
+    }
+    tx.commit().map_err(|e| format!("提交事务失败: {}", e))?;
+    Ok(run_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run_row(config_hash: &str) -> RunRow<'static> {
+        RunRow {
+            config_hash: config_hash.to_string(),
+            target: "http://127.0.0.1:8080/",
+            concurrency: 10,
+            duration_secs: 2.0,
+            total_requests: 3,
+            successful_requests: 2,
+            failed_requests: 1,
+            rps: Some(1.5),
+            latency: Some([12.5, 10.0, 15.0, 15.0, 15.0, 15.0]),
+            config: "{}".to_string(),
+            report: "{}".to_string(),
+        }
+    }
+
+    #[test]
+    fn append_run_creates_schema_and_appends() {
+        let path = std::env::temp_dir().join(format!("ab-sqlite-test-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let requests = [
+            RequestRow { index: 0, timestamp_ms: 1000, duration_ms: 10.0, success: true, status: Some(200), error: None },
+            RequestRow {
+                index: 1,
+                timestamp_ms: 1010,
+                duration_ms: 30.0,
+                success: false,
+                status: None,
+                error: Some("连接超时 (Connect-Timeout)".to_string()),
+            },
+        ];
+        let first = append_run(&path, &run_row("0123456789abcdef"), &requests).unwrap();
+        // 再次写入同一个文件时表已存在，只追加数据
+        let second = append_run(&path, &run_row("0123456789abcdef"), &[]).unwrap();
+        assert_eq!(second, first + 1);
+
+        let conn = Connection::open(&path).unwrap();
+        let (hash, concurrency, successful, p50, p99): (String, i64, i64, f64, f64) = conn
+            .query_row(
+                "SELECT config_hash, concurrency, successful_requests, p50_ms, p99_ms FROM runs WHERE id = ?1",
+                [first],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?)),
+            )
+            .unwrap();
+        assert_eq!((hash.as_str(), concurrency, successful, p50, p99), ("0123456789abcdef", 10, 2, 10.0, 15.0));
+        let rows: Vec<(i64, bool, Option<i64>, Option<String>)> = conn
+            .prepare("SELECT idx, success, status, error FROM requests WHERE run_id = ?1 ORDER BY idx")
+            .unwrap()
+            .query_map([first], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            rows,
+            [(0, true, Some(200), None), (1, false, None, Some("连接超时 (Connect-Timeout)".to_string()))]
+        );
+        let count: i64 = conn.query_row("SELECT COUNT(*) FROM requests WHERE run_id = ?1", [second], |row| row.get(0)).unwrap();
+        assert_eq!(count, 0);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn config_hash_is_stable_fnv1a() {
+        assert_eq!(config_hash(""), "cbf29ce484222325");
+        assert_eq!(config_hash("{}"), config_hash("{}"));
+        assert_ne!(config_hash("{\"concurrency\":1}"), config_hash("{\"concurrency\":2}"));
+    }
+}
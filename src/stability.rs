@@ -0,0 +1,102 @@
+// src/stability.rs
+
+//! --stop-when-stable：压测进行中按滑动窗口观察延迟，结果稳定后提前停止派发请求。
+//!
+//! 每完成一个窗口的成功请求 (不含预热) 计算一次这一窗口的 P50 和 P99，最近 [`STABLE_WINDOWS`] 个窗口的
+//! P50、P99 各自的波动都不超过阈值时认为结果已经稳定。延迟与报告一样以毫秒统计，相差不超过 1 ms 视为没有变化。
+//! 稳定后各任务不再发起新请求，进行中的请求照常完成并计入结果。
+
+use hdrhistogram::Histogram;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+/// 需要连续保持稳定的窗口数
+pub const STABLE_WINDOWS: usize = 3;
+
+/// 所有任务共享的稳定性判定
+pub struct StabilityMonitor {
+    window_size: usize,
+    threshold_pct: f64,
+    state: Mutex<WindowState>,
+    stable: AtomicBool,
+    stable_after: AtomicUsize, // 判定稳定时已经完成的请求数 (包括预热和失败的请求)
+}
+
+struct WindowState {
+    histogram: Histogram<u64>,  // 当前窗口的延迟 (毫秒)
+    recent: VecDeque<(u64, u64)>, // 最近几个窗口的 (P50, P99)
+}
+
+impl StabilityMonitor {
+    pub fn new(window_size: usize, threshold_pct: f64) -> Self {
+        StabilityMonitor {
+            window_size,
+            threshold_pct,
+            state: Mutex::new(WindowState { histogram: Histogram::<u64>::new(3).unwrap(), recent: VecDeque::new() }),
+            stable: AtomicBool::new(false),
+            stable_after: AtomicUsize::new(0),
+        }
+    }
+
+    /// 记录一个成功请求的延迟，completed 是包括该请求在内已经完成的请求数
+    pub fn record(&self, latency_ms: u64, completed: usize) {
+        if self.is_stable() {
+            return;
+        }
+        let Ok(mut state) = self.state.lock() else {
+            return;
+        };
+        let _ = state.histogram.record(latency_ms);
+        if (state.histogram.len() as usize) < self.window_size {
+            return;
+        }
+        let percentiles = (state.histogram.value_at_percentile(50.0), state.histogram.value_at_percentile(99.0));
+        state.histogram.reset();
+        state.recent.push_back(percentiles);
+        if state.recent.len() > STABLE_WINDOWS {
+            state.recent.pop_front();
+        }
+        if is_stable(state.recent.make_contiguous(), self.threshold_pct) {
+            self.stable_after.store(completed, Ordering::Relaxed);
+            self.stable.store(true, Ordering::Relaxed);
+        }
+    }
+
+    pub fn is_stable(&self) -> bool {
+        self.stable.load(Ordering::Relaxed)
+    }
+
+    /// 判定稳定时已经完成的请求数，还没有稳定时为 None
+    pub fn stable_after(&self) -> Option<usize> {
+        self.is_stable().then(|| self.stable_after.load(Ordering::Relaxed))
+    }
+}
+
+/// 最近的窗口是否已经稳定：至少有 STABLE_WINDOWS 个窗口，且其中 P50、P99 各自的波动 ((最大值 - 最小值) / 最小值)
+/// 都不超过 threshold_pct，或者相差不超过 1 ms
+pub fn is_stable(recent: &[(u64, u64)], threshold_pct: f64) -> bool {
+    if recent.len() < STABLE_WINDOWS {
+        return false;
+    }
+    let within = |values: Vec<u64>| {
+        let (min, max) = (values.iter().min().copied().unwrap_or(0), values.iter().max().copied().unwrap_or(0));
+        max - min <= 1 || (max - min) as f64 <= min as f64 * threshold_pct / 100.0
+    };
+    let windows = &recent[recent.len() - STABLE_WINDOWS..];
+    within(windows.iter().map(|&(p50, _)| p50).collect()) && within(windows.iter().map(|&(_, p99)| p99).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stability_needs_consecutive_windows_within_threshold() {
+        assert!(!is_stable(&[(10, 50), (10, 50)], 5.0)); // 窗口数不足
+        assert!(is_stable(&[(100, 200), (104, 209), (102, 200)], 5.0));
+        assert!(!is_stable(&[(100, 200), (104, 240), (102, 200)], 5.0));
+        assert!(is_stable(&[(1, 2), (0, 3), (1, 3)], 5.0)); // 相差不超过 1 ms
+        assert!(is_stable(&[(500, 900), (100, 200), (101, 201), (100, 200)], 5.0)); // 只看最近的窗口
+    }
+}
@@ -0,0 +1,227 @@
+// src/targets.rs
+
+//! Vegeta 风格 targets 文件的解析。
+//!
+//! 支持的格式子集：
+//!
+//! ```text
+//! # 以 # 开头的行是注释
+//! GET http://localhost:8080/users?id=1
+//! X-Account-ID: 8675309
+//!
+//! POST http://localhost:8080/users
+//! Content-Type: application/json
+//! @/path/to/body.json
+//! ```
+//!
+//! - 每个目标以 `METHOD URL` 行开始，目标之间可以用空行分隔
+//! - 之后可以跟任意多行 `Key: Value` 格式的 Header
+//! - 可选的 `@<路径>` 行指定请求体文件 (相对路径相对于当前工作目录)，必须是该目标的最后一行
+//!
+//! 不支持 Vegeta 的 JSON targets 格式，遇到无法识别的行会报告行号并返回错误。
+//!
+//! 另外支持 JSON Lines 格式 (--targets-jsonl)，每行一个 JSON 对象，便于由程序生成：
+//!
+//! ```text
+//! {"method": "GET", "url": "http://localhost:8080/users?id=1", "headers": {"X-Account-ID": "8675309"}}
+//! {"method": "POST", "url": "http://localhost:8080/users", "body": {"name": "a"}, "expect_status": [201, 409]}
+//! ```
+//!
+//! - 只有 url 是必需的，method 默认为 GET
+//! - body 为字符串时原样发送，为其他 JSON 值时发送其 JSON 文本
+//! - expect_status 为单个状态码或状态码数组，指定后只有这些状态码视为成功 (默认 2xx)
+//! - timeout 为该目标的超时 (秒，可以带小数)，覆盖 --timeout
+//! - 空行和以 # 开头的行会被忽略
+
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+use url::Url;
+
+/// 从 targets 文件解析出的单个请求定义
+#[derive(Debug)]
+pub struct Target {
+    pub method: String,
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Option<Vec<u8>>,
+    pub expect_status: Option<Vec<u16>>, // 视为成功的状态码，None 表示默认的 2xx
+    pub timeout: Option<Duration>,       // 该目标的超时，None 表示使用 --timeout
+}
+
+/// 读取并解析 targets 文件
+pub fn load_targets(path: &Path) -> Result<Vec<Target>, String> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| format!("无法读取 targets 文件 {}: {}", path.display(), e))?;
+    let targets = parse_targets(&content)?;
+    if targets.is_empty() {
+        return Err(format!("targets 文件 {} 中没有任何目标", path.display()));
+    }
+    Ok(targets)
+}
+
+/// 解析 targets 文件内容
+pub fn parse_targets(content: &str) -> Result<Vec<Target>, String> {
+    let mut targets: Vec<Target> = Vec::new();
+    // 当前目标是否已经读取了请求体 (请求体之后不能再出现 Header)
+    let mut body_seen = false;
+
+    for (i, raw_line) in content.lines().enumerate() {
+        let line_no = i + 1;
+        let line = raw_line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        if line.starts_with('#') {
+            continue;
+        }
+
+        if let Some((method, url)) = parse_request_line(line) {
+            Url::parse(url).map_err(|e| format!("targets 第 {} 行: 无效的URL \"{}\": {}", line_no, url, e))?;
+            targets.push(Target {
+                method: method.to_string(),
+                url: url.to_string(),
+                headers: Vec::new(),
+                body: None,
+                expect_status: None,
+                timeout: None,
+            });
+            body_seen = false;
+            continue;
+        }
+
+        let Some(target) = targets.last_mut() else {
+            return Err(format!("targets 第 {} 行: 期望 \"METHOD URL\"，实际为 \"{}\"", line_no, line));
+        };
+        if body_seen {
+            return Err(format!("targets 第 {} 行: 请求体 (@文件) 必须是目标的最后一行", line_no));
+        }
+
+        if let Some(body_path) = line.strip_prefix('@') {
+            let body = fs::read(body_path.trim())
+                .map_err(|e| format!("targets 第 {} 行: 无法读取请求体文件 {}: {}", line_no, body_path.trim(), e))?;
+            target.body = Some(body);
+            body_seen = true;
+        } else if line.contains(':') {
+            let header = crate::parse_header(line).map_err(|e| format!("targets 第 {} 行: {}", line_no, e))?;
+            target.headers.push(header);
+        } else {
+            return Err(format!("targets 第 {} 行: 不支持的指令 \"{}\"", line_no, line));
+        }
+    }
+
+    Ok(targets)
+}
+
+/// 识别 "METHOD URL" 请求行，方法名必须全部为大写字母
+fn parse_request_line(line: &str) -> Option<(&str, &str)> {
+    let (method, url) = line.split_once(char::is_whitespace)?;
+    if method.is_empty() || !method.chars().all(|c| c.is_ascii_uppercase()) {
+        return None;
+    }
+    let url = url.trim();
+    if url.is_empty() || url.contains(char::is_whitespace) {
+        return None;
+    }
+    Some((method, url))
+}
+
+/// --targets-jsonl 中的一行
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct JsonTarget {
+    #[serde(default = "default_method")]
+    method: String,
+    url: String,
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+    body: Option<serde_json::Value>,
+    expect_status: Option<ExpectStatus>,
+    timeout: Option<f64>,
+}
+
+/// expect_status 可以是单个状态码或状态码数组
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ExpectStatus {
+    One(u16),
+    Many(Vec<u16>),
+}
+
+fn default_method() -> String {
+    "GET".to_string()
+}
+
+/// 读取并解析 JSON Lines 格式的 targets 文件
+pub fn load_targets_jsonl(path: &Path) -> Result<Vec<Target>, String> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| format!("无法读取 targets 文件 {}: {}", path.display(), e))?;
+    let targets = parse_targets_jsonl(&content)?;
+    if targets.is_empty() {
+        return Err(format!("targets 文件 {} 中没有任何目标", path.display()));
+    }
+    Ok(targets)
+}
+
+/// 解析 JSON Lines 格式的 targets 内容
+pub fn parse_targets_jsonl(content: &str) -> Result<Vec<Target>, String> {
+    let mut targets = Vec::new();
+    for (i, raw_line) in content.lines().enumerate() {
+        let line_no = i + 1;
+        let line = raw_line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let target: JsonTarget = serde_json::from_str(line).map_err(|e| format!("targets 第 {} 行: {}", line_no, e))?;
+        Url::parse(&target.url).map_err(|e| format!("targets 第 {} 行: 无效的URL \"{}\": {}", line_no, target.url, e))?;
+        let expect_status = match target.expect_status {
+            Some(ExpectStatus::One(status)) => Some(vec![status]),
+            Some(ExpectStatus::Many(statuses)) if statuses.is_empty() => {
+                return Err(format!("targets 第 {} 行: expect_status 不能为空数组", line_no));
+            }
+            Some(ExpectStatus::Many(statuses)) => Some(statuses),
+            None => None,
+        };
+        if let Some(status) = expect_status.iter().flatten().find(|status| !(100..=999).contains(*status)) {
+            return Err(format!("targets 第 {} 行: 无效的状态码 {}", line_no, status));
+        }
+        let timeout = match target.timeout {
+            Some(secs) => match Duration::try_from_secs_f64(secs) {
+                Ok(timeout) if !timeout.is_zero() => Some(timeout),
+                _ => return Err(format!("targets 第 {} 行: 无效的超时 {} (应为大于 0 的秒数)", line_no, secs)),
+            },
+            None => None,
+        };
+        targets.push(Target {
+            method: target.method.to_uppercase(),
+            url: target.url,
+            headers: target.headers.into_iter().collect(),
+            body: target.body.map(|body| match body {
+                serde_json::Value::String(text) => text.into_bytes(),
+                other => other.to_string().into_bytes(),
+            }),
+            expect_status,
+            timeout,
+        });
+    }
+    Ok(targets)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn targets_jsonl_timeout_overrides_per_target() {
+        let targets = parse_targets_jsonl(
+            "{\"url\": \"http://localhost/fast\", \"timeout\": 0.25}\n{\"url\": \"http://localhost/slow\"}\n",
+        )
+        .unwrap();
+        assert_eq!(targets[0].timeout, Some(Duration::from_millis(250)));
+        assert_eq!(targets[1].timeout, None);
+        assert!(parse_targets_jsonl("{\"url\": \"http://localhost/\", \"timeout\": 0}").is_err());
+        assert!(parse_targets_jsonl("{\"url\": \"http://localhost/\", \"timeout\": 1e300}").is_err());
+    }
+}
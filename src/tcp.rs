@@ -0,0 +1,121 @@
+// src/tcp.rs
+
+//! 原始 TCP 压测：不经过 HTTP，直接测量 TCP 连接的建立以及可选的一次请求/回复往返。
+//!
+//! 每个请求新建一个连接：连接建立后如果有请求数据就整体写出，再等待对端的第一段回复 (读到任意字节即可)，
+//! 然后关闭连接。适合压测自定义二进制协议的服务端或 L4 代理。
+
+use crate::connection::ConnectRateLimiter;
+use std::io;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+/// 读取回复时使用的缓冲区大小，只需读到第一段回复
+const REPLY_BUFFER_SIZE: usize = 16 * 1024;
+
+/// TCP 模式的超时设置
+pub struct TcpOptions {
+    pub connect_timeout: Duration,
+    pub timeout: Duration, // 发送请求并读取回复的超时
+    pub nodelay: bool,     // 是否设置 TCP_NODELAY
+    pub connect_limiter: Option<Arc<ConnectRateLimiter>>, // --connect-rate
+}
+
+/// 一次成功的交互
+pub struct TcpExchange {
+    pub connect: Duration,  // 建立连接的耗时
+    pub reply_bytes: usize, // 第一段回复的字节数，未发送数据时为 0
+}
+
+/// 一次失败的交互，连接已建立时附带建立连接的耗时
+pub struct TcpFailure {
+    pub connect: Option<Duration>,
+    pub error: String,
+}
+
+/// 解析 tcp://host:port 形式的目标地址
+pub fn parse_address(url: &str) -> Result<(String, u16), String> {
+    let parsed = url::Url::parse(url).map_err(|e| format!("无效的 TCP 地址 {}: {}", url, e))?;
+    if parsed.scheme() != "tcp" {
+        return Err(format!("TCP 模式的 URL 必须以 tcp:// 开头: {}", url));
+    }
+    let host = parsed.host_str().ok_or_else(|| format!("TCP 地址中缺少主机名: {}", url))?;
+    let port = parsed.port().ok_or_else(|| format!("TCP 地址中缺少端口: {}", url))?;
+    // IPv6 地址在 URL 中带有方括号，连接时需要去掉
+    Ok((host.trim_start_matches('[').trim_end_matches(']').to_string(), port))
+}
+
+/// 连接目标地址，有 payload 时发送并等待回复
+pub async fn exchange(url: &str, payload: Option<&[u8]>, options: &TcpOptions) -> Result<TcpExchange, TcpFailure> {
+    let failure = |connect, error| TcpFailure { connect, error };
+    let (host, port) = parse_address(url).map_err(|e| failure(None, e))?;
+
+    if let Some(limiter) = &options.connect_limiter {
+        limiter.acquire().await;
+    }
+    let start = Instant::now();
+    let mut stream = match tokio::time::timeout(options.connect_timeout, TcpStream::connect((host.as_str(), port))).await {
+        Ok(Ok(stream)) => stream,
+        Ok(Err(e)) => return Err(failure(None, classify("连接失败", &e))),
+        Err(_) => return Err(failure(None, "连接超时 (Connect-Timeout)".to_string())),
+    };
+    let connect = start.elapsed();
+    let _ = stream.set_nodelay(options.nodelay);
+
+    let Some(payload) = payload else {
+        return Ok(TcpExchange { connect, reply_bytes: 0 });
+    };
+    let round_trip = async {
+        stream.write_all(payload).await.map_err(|e| classify("发送失败", &e))?;
+        let mut buf = vec![0u8; REPLY_BUFFER_SIZE];
+        match stream.read(&mut buf).await {
+            Ok(0) => Err("对端关闭连接，未收到回复".to_string()),
+            Ok(read) => Ok(read),
+            Err(e) => Err(classify("读取回复失败", &e)),
+        }
+    };
+    match tokio::time::timeout(options.timeout, round_trip).await {
+        Ok(Ok(reply_bytes)) => Ok(TcpExchange { connect, reply_bytes }),
+        Ok(Err(error)) => Err(failure(Some(connect), error)),
+        Err(_) => Err(failure(Some(connect), "读取超时 (Read-Timeout)".to_string())),
+    }
+}
+
+/// 将常见的 IO 错误归类为固定的错误信息，便于在报告中聚合
+fn classify(context: &str, error: &io::Error) -> String {
+    match error.kind() {
+        io::ErrorKind::ConnectionRefused => "连接被拒绝 (Connection-Refused)".to_string(),
+        io::ErrorKind::ConnectionReset => "连接被重置 (Connection-Reset)".to_string(),
+        io::ErrorKind::ConnectionAborted => "连接被中止 (Connection-Aborted)".to_string(),
+        io::ErrorKind::BrokenPipe => "连接已断开 (Broken-Pipe)".to_string(),
+        io::ErrorKind::HostUnreachable | io::ErrorKind::NetworkUnreachable => "目标不可达 (Unreachable)".to_string(),
+        _ => format!("{}: {}", context, error),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_address_accepts_tcp_urls() {
+        assert_eq!(parse_address("tcp://127.0.0.1:9000"), Ok(("127.0.0.1".to_string(), 9000)));
+        assert_eq!(parse_address("tcp://echo.example.com:7"), Ok(("echo.example.com".to_string(), 7)));
+        assert_eq!(parse_address("tcp://[::1]:9000"), Ok(("::1".to_string(), 9000)));
+    }
+
+    #[test]
+    fn parse_address_rejects_bare_addresses_and_other_schemes() {
+        assert!(parse_address("127.0.0.1:9000").unwrap_err().starts_with("无效的 TCP 地址"));
+        assert!(parse_address("localhost:9000").unwrap_err().starts_with("TCP 模式的 URL 必须以 tcp:// 开头"));
+        assert!(parse_address("http://127.0.0.1:9000").unwrap_err().starts_with("TCP 模式的 URL 必须以 tcp:// 开头"));
+    }
+
+    #[test]
+    fn parse_address_requires_a_port() {
+        assert_eq!(parse_address("tcp://127.0.0.1"), Err("TCP 地址中缺少端口: tcp://127.0.0.1".to_string()));
+        assert_eq!(parse_address("tcp://[::1]"), Err("TCP 地址中缺少端口: tcp://[::1]".to_string()));
+    }
+}
@@ -0,0 +1,96 @@
+// src/timeline.rs
+
+//! --record-timeline / --replay-timeline：记录每个请求实际派发的时间，并在之后按记录的时间重新派发。
+//! 文件每行一个请求的派发时间 (毫秒，可以带小数)，以 # 开头的行是注释，空行会被忽略：
+//!
+//! ```text
+//! # 相对压测开始的派发时间 (毫秒)
+//! 0.000
+//! 12.500
+//! 13.120
+//! ```
+//!
+//! 回放时以最早的时间为起点，因此也可以直接使用从生产日志中提取的 Unix 时间戳 (毫秒)。行的顺序无关紧要。
+
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// 记录压测中每个请求的派发时间，结束后写入文件
+pub struct TimelineRecorder {
+    start: Instant,
+    offsets: Mutex<Vec<Duration>>,
+}
+
+impl TimelineRecorder {
+    pub fn new(start: Instant) -> Self {
+        TimelineRecorder { start, offsets: Mutex::new(Vec::new()) }
+    }
+
+    /// 记录一个请求在此刻派发
+    pub fn record(&self) {
+        let offset = self.start.elapsed();
+        if let Ok(mut offsets) = self.offsets.lock() {
+            offsets.push(offset);
+        }
+    }
+
+    /// 按时间顺序写入所有记录，返回记录的请求数
+    pub fn write(&self, path: &Path) -> std::io::Result<usize> {
+        let mut offsets = self.offsets.lock().map(|offsets| offsets.clone()).unwrap_or_default();
+        offsets.sort();
+        let mut out = std::io::BufWriter::new(std::fs::File::create(path)?);
+        writeln!(out, "# 相对压测开始的派发时间 (毫秒)")?;
+        for offset in &offsets {
+            writeln!(out, "{:.3}", offset.as_secs_f64() * 1000.0)?;
+        }
+        out.flush()?;
+        Ok(offsets.len())
+    }
+}
+
+/// 读取并解析时间线文件
+pub fn load_timeline(path: &Path) -> Result<Vec<Duration>, String> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| format!("无法读取时间线文件 {}: {}", path.display(), e))?;
+    let offsets = parse_timeline(&content)?;
+    if offsets.is_empty() {
+        return Err(format!("时间线文件 {} 中没有任何请求", path.display()));
+    }
+    Ok(offsets)
+}
+
+/// 解析时间线文件内容，返回按时间排序、以最早的时间为起点的派发时间
+pub fn parse_timeline(content: &str) -> Result<Vec<Duration>, String> {
+    let mut millis = Vec::new();
+    for (i, raw_line) in content.lines().enumerate() {
+        let line = raw_line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let value = line
+            .parse::<f64>()
+            .ok()
+            .filter(|value| Duration::try_from_secs_f64(*value / 1000.0).is_ok())
+            .ok_or_else(|| format!("时间线文件第 {} 行: 无效的时间 \"{}\" (应为非负的毫秒数)", i + 1, line))?;
+        millis.push(value);
+    }
+    millis.sort_by(f64::total_cmp);
+    let first = millis.first().copied().unwrap_or_default();
+    Ok(millis.into_iter().map(|value| Duration::from_secs_f64((value - first) / 1000.0)).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timeline_is_sorted_and_starts_at_zero() {
+        let offsets = parse_timeline("# 注释\n1700000000500\n\n1700000000000\n1700000000250.5\n").unwrap();
+        assert_eq!(offsets, [Duration::ZERO, Duration::from_micros(250_500), Duration::from_millis(500)]);
+        assert!(parse_timeline("10\n-1\n").is_err());
+        assert!(parse_timeline("10\n1e300\n").is_err());
+        assert!(parse_timeline("").unwrap().is_empty());
+    }
+}
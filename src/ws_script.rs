@@ -0,0 +1,72 @@
+// src/ws_script.rs
+
+//! --ws-script 脚本文件的解析。脚本描述一次 WebSocket 会话中依次发送的消息：
+//!
+//! ```text
+//! # 以 # 开头的行是注释，空行会被忽略
+//! {"op":"subscribe","channel":"ticker"} => subscribed
+//! @1000 {"op":"ping"} => pong
+//! @1000 {"op":"ping"}
+//! ```
+//!
+//! - 每行一条文本消息，按顺序在同一个连接上发送
+//! - 可选的 `@<毫秒>` 前缀表示发送前先等待的时间，用于模拟客户端的周期性命令
+//! - 可选的 ` => <子串>` 后缀表示发送后等待一条包含该子串的数据消息 (期间收到的其他消息被跳过)，
+//!   超时 (--timeout) 前没有等到则会话失败；没有后缀的消息发送后不等待回复
+
+use std::path::Path;
+use std::time::Duration;
+
+/// 期望回复的分隔符
+const EXPECT_SEPARATOR: &str = " => ";
+
+/// 脚本中的一条消息
+#[derive(Debug, PartialEq)]
+pub struct ScriptStep {
+    pub delay: Option<Duration>, // 发送前等待的时间
+    pub message: String,
+    pub expect: Option<String>, // 需要等到的回复所包含的子串，None 表示不等待回复
+}
+
+/// 读取并解析脚本文件
+pub fn load_script(path: &Path) -> Result<Vec<ScriptStep>, String> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| format!("无法读取 WebSocket 脚本 {}: {}", path.display(), e))?;
+    let steps = parse_script(&content)?;
+    if steps.is_empty() {
+        return Err(format!("WebSocket 脚本 {} 中没有任何消息", path.display()));
+    }
+    Ok(steps)
+}
+
+/// 解析脚本内容
+pub fn parse_script(content: &str) -> Result<Vec<ScriptStep>, String> {
+    let mut steps = Vec::new();
+    for (i, raw_line) in content.lines().enumerate() {
+        let line_no = i + 1;
+        let line = raw_line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (delay, rest) = match line.strip_prefix('@') {
+            Some(rest) => {
+                let (ms, message) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+                let ms = ms
+                    .parse::<u64>()
+                    .map_err(|_| format!("WebSocket 脚本第 {} 行: 无效的延迟 \"@{}\"，期望毫秒数", line_no, ms))?;
+                (Some(Duration::from_millis(ms)), message.trim_start())
+            }
+            None => (None, line),
+        };
+        let (message, expect) = match rest.rsplit_once(EXPECT_SEPARATOR) {
+            Some((message, expect)) => (message.trim_end(), Some(expect.trim().to_string())),
+            None => (rest, None),
+        };
+        if message.is_empty() {
+            return Err(format!("WebSocket 脚本第 {} 行: 缺少要发送的消息", line_no));
+        }
+        steps.push(ScriptStep { delay, message: message.to_string(), expect });
+    }
+    Ok(steps)
+}